@@ -0,0 +1,172 @@
+extern crate ray_tracing;
+
+use crate::ray_tracing::camera::Camera;
+use crate::ray_tracing::collections::*;
+use crate::ray_tracing::hitable::{HitRecord, Hitable, TraversalStats};
+use crate::ray_tracing::material::{Dielectric, Lambertian, Metal};
+use crate::ray_tracing::num::{Int, Num, MAX_NUM};
+use crate::ray_tracing::sphere::Sphere;
+use crate::ray_tracing::vec3::Vec3;
+
+use rand::prelude::*;
+
+use std::time::{Duration, Instant};
+
+const NX: Int = 400;
+const NY: Int = 300;
+
+/// Builds the primitives of a scene.
+type Scene = fn() -> Vec<Box<dyn Hitable>>;
+
+/// The scene of `examples/cover.rs`, with the floor kept as a large sphere so every accelerator
+/// can hold it.
+fn cover() -> Vec<Box<dyn Hitable>> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut world = HitableVec::default();
+
+    world.add(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(0.5, 0.5, 0.5),
+    ));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rng.gen::<Num>();
+            let center = Vec3::new(
+                a as Num + 0.9 * rng.gen::<Num>(),
+                0.2,
+                b as Num + 0.9 * rng.gen::<Num>(),
+            );
+
+            if (center - Vec3::new(4.0, 0.2, 0.0)).len() > 0.9 {
+                if choose_mat < 0.8 {
+                    world.add(Sphere::new(center, 0.2, Lambertian::new(0.5, 0.3, 0.2)));
+                } else if choose_mat < 0.95 {
+                    world.add(Sphere::new(center, 0.2, Metal::new(0.7, 0.6, 0.5, 0.1)));
+                } else {
+                    world.add(Sphere::new(center, 0.2, Dielectric::new(1.5)));
+                }
+            }
+        }
+    }
+
+    world.add(Sphere::new(
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        Dielectric::new(1.5),
+    ));
+    world.add(Sphere::new(
+        Vec3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Lambertian::new(0.4, 0.2, 0.1),
+    ));
+    world.add(Sphere::new(
+        Vec3::new(4.0, 1.0, 0.0),
+        1.0,
+        Metal::new(0.7, 0.6, 0.5, 0.0),
+    ));
+
+    world.into_vec()
+}
+
+/// A dense field of small spheres filling a cube.
+fn sphere_field() -> Vec<Box<dyn Hitable>> {
+    let mut world = HitableVec::default();
+    for x in 0..30 {
+        for y in 0..30 {
+            for z in 0..30 {
+                let center = Vec3::new(x as Num - 15.0, y as Num - 15.0, z as Num - 15.0) * 0.3;
+                world.add(Sphere::new(center, 0.05, Lambertian::new(0.5, 0.5, 0.5)));
+            }
+        }
+    }
+    world.into_vec()
+}
+
+fn camera(lookfrom: Vec3) -> Camera {
+    Camera::new(
+        lookfrom,
+        Vec3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        (NX as Num) / (NY as Num),
+        0.0,
+        10.0,
+    )
+}
+
+fn bench(name: &str, build: impl FnOnce() -> Box<dyn Hitable>, camera: &Camera) {
+    let start = Instant::now();
+    let world = build();
+    let build_time = start.elapsed();
+
+    let mut rng = rand::thread_rng();
+    let mut stats = TraversalStats::default();
+    let mut hits = 0;
+    let start = Instant::now();
+    for j in 0..NY {
+        for i in 0..NX {
+            let u = (i as Num + 0.5) / NX as Num;
+            let v = (j as Num + 0.5) / NY as Num;
+            let r = camera.get_ray(u, v, &mut rng);
+            if world.hit_counting(&r, 0.0, MAX_NUM, &mut HitRecord::default(), &mut stats) {
+                hits += 1;
+            }
+        }
+    }
+    let trace_time = start.elapsed();
+
+    let rays = (NX * NY) as Num;
+    println!(
+        "{:<12} build {:>9.3} ms  trace {:>9.3} ms  {:>8.1} node tests/ray  {:>8.1} primitive tests/ray  {} hits",
+        name,
+        millis(build_time),
+        millis(trace_time),
+        stats.node_tests as Num / rays,
+        stats.primitive_tests as Num / rays,
+        hits,
+    );
+}
+
+fn millis(duration: Duration) -> Num {
+    duration.as_secs_f64() as Num * 1000.0
+}
+
+fn main() {
+    let scenes: [(&str, Scene, Vec3); 2] = [
+        ("cover", cover, Vec3::new(13.0, 2.0, 3.0)),
+        ("sphere field", sphere_field, Vec3::new(20.0, 15.0, 25.0)),
+    ];
+
+    for (name, scene, lookfrom) in scenes.iter() {
+        println!("{} ({} primitives)", name, scene().len());
+        let camera = camera(*lookfrom);
+
+        bench(
+            "HitableVec",
+            || Box::new(HitableVec { inner: scene() }),
+            &camera,
+        );
+        bench(
+            "BVHTree",
+            || Box::new(BVHTree::new(scene(), 0.0, MAX_NUM, &mut rand::thread_rng())),
+            &camera,
+        );
+        bench(
+            "KdTree",
+            || Box::new(KdTree::new(scene(), 0.0, MAX_NUM)),
+            &camera,
+        );
+        bench(
+            "Grid",
+            || Box::new(Grid::new(scene(), 0.0, MAX_NUM, false)),
+            &camera,
+        );
+        bench(
+            "HGrid",
+            || Box::new(Grid::new(scene(), 0.0, MAX_NUM, true)),
+            &camera,
+        );
+    }
+}
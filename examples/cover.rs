@@ -4,6 +4,7 @@ use crate::ray_tracing::camera::Camera;
 use crate::ray_tracing::collections::HitableVec;
 use crate::ray_tracing::material::{Dielectric, Lambertian, Metal};
 use crate::ray_tracing::num::Num;
+use crate::ray_tracing::plane::Plane;
 use crate::ray_tracing::render;
 use crate::ray_tracing::sphere::Sphere;
 use crate::ray_tracing::vec3::Vec3;
@@ -34,9 +35,9 @@ fn main() -> std::io::Result<()> {
         dist_to_focus,
     );
 
-    world.add(Sphere::new(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
+    world.add(Plane::new(
+        Vec3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
         Lambertian::new(0.5, 0.5, 0.5),
     ));
 
@@ -0,0 +1,64 @@
+extern crate ray_tracing;
+
+use crate::ray_tracing::camera::Camera;
+use crate::ray_tracing::collections::*;
+use crate::ray_tracing::hitable::Hitable;
+use crate::ray_tracing::material::{Lambertian, Metal};
+use crate::ray_tracing::num::{Num, MAX_NUM};
+use crate::ray_tracing::render;
+use crate::ray_tracing::sphere::Sphere;
+use crate::ray_tracing::transform::Transform;
+use crate::ray_tracing::vec3::Vec3;
+
+use std::sync::Arc;
+
+fn main() -> std::io::Result<()> {
+    let mut rng = rand::thread_rng();
+
+    let nx = 800;
+    let ny = 600;
+    let ns = 100;
+
+    let camera = Camera::new(
+        Vec3::new(13.0, 6.0, 3.0),
+        Vec3::default(),
+        Vec3::new(0.0, 1.0, 0.0),
+        30.0,
+        (nx as Num) / (ny as Num),
+        0.0,
+        10.0,
+    );
+
+    let mut part = HitableVec::default();
+    part.add(Sphere::new(
+        Vec3::new(0.0, 0.2, 0.0),
+        0.2,
+        Lambertian::new(0.8, 0.3, 0.1),
+    ));
+    part.add(Sphere::new(
+        Vec3::new(0.0, 0.5, 0.0),
+        0.1,
+        Metal::new(0.7, 0.6, 0.5, 0.0),
+    ));
+    // The scene is only ever shared between threads, never sent.
+    #[allow(clippy::arc_with_non_send_sync)]
+    let part: Arc<dyn Hitable> = Arc::new(BVHTree::new(part.into_vec(), 0.0, MAX_NUM, &mut rng));
+
+    let mut instances = HitableVec::default();
+    instances.add(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(0.5, 0.5, 0.5),
+    ));
+    for a in -10..10 {
+        for b in -10..10 {
+            let transform = Transform::translate(Vec3::new(a as Num, 0.0, b as Num))
+                * Transform::scale(Vec3::from_scalar(1.0 + 0.05 * (a + b) as Num));
+            instances.add(Instance::new(part.clone(), transform));
+        }
+    }
+
+    let world = BVHTree::new(instances.into_vec(), 0.0, MAX_NUM, &mut rng);
+
+    render("instancing.ppm", world, camera, nx, ny, ns)
+}
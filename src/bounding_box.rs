@@ -1,5 +1,6 @@
 use crate::hitable::{HitRecord, Hitable};
 use crate::num::*;
+use crate::packet::RayPacket;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
@@ -14,24 +15,28 @@ impl BoundingBox {
         BoundingBox { a, b }
     }
 
-    pub fn max(&self) -> Vec3 {
+    pub fn min(&self) -> Vec3 {
         self.a
     }
 
-    pub fn min(&self) -> Vec3 {
+    pub fn max(&self) -> Vec3 {
         self.b
     }
 
+    pub fn area(&self) -> Num {
+        let d = self.b - self.a;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
     pub fn surrounding_box(&self, other: &Self) -> Self {
         let small = self.a.min(&other.a);
         let big = self.b.max(&other.b);
 
         Self::new(small, big)
     }
-}
 
-impl Hitable for BoundingBox {
-    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, _: &mut HitRecord<'a>) -> bool {
+    /// The interval of `r` inside the box, clipped to `t_min` and `t_max`.
+    pub fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, Num)> {
         let ori = r.origin();
         let dir = r.direction();
 
@@ -40,9 +45,37 @@ impl Hitable for BoundingBox {
 
         let (v0, v1) = p.min_max(&q);
 
-        min(v1.x(), t_max) > max(v0.x(), t_min)
-            || min(v1.y(), t_max) > max(v0.y(), t_min)
-            || min(v1.z(), t_max) > max(v0.z(), t_min)
+        let t0 = max(max(v0.x(), t_min), max(v0.y(), v0.z()));
+        let t1 = min(min(v1.x(), t_max), min(v1.y(), v1.z()));
+
+        if t1 >= t0 {
+            Some((t0, t1))
+        } else {
+            None
+        }
+    }
+
+    /// The lanes of `packet` that cross the box between `t_min` and their `t_max`.
+    pub fn hit_packet(&self, packet: &RayPacket, t_min: Num, t_max: Numx4) -> Maskx4 {
+        let origin = packet.origin();
+        let inv_direction = packet.inv_direction();
+
+        let mut t0 = Numx4::splat(t_min);
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let p = (self.a.get(axis) - origin[axis]) * inv_direction[axis];
+            let q = (self.b.get(axis) - origin[axis]) * inv_direction[axis];
+            t0 = t0.max(p.min(q));
+            t1 = t1.min(p.max(q));
+        }
+
+        t1.ge(t0)
+    }
+}
+
+impl Hitable for BoundingBox {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, _: &mut HitRecord<'a>) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
     }
 
     fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
@@ -20,6 +20,8 @@ pub struct Camera {
     lens_radius: Num,
     u: Vec3,
     v: Vec3,
+    time0: Num,
+    time1: Num,
 }
 
 impl Camera {
@@ -57,15 +59,31 @@ impl Camera {
             lens_radius,
             u,
             v,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
 
+    /// Opens the shutter at `time0` and closes it at `time1`, the rays are cast at random times
+    /// in between.
+    pub fn with_shutter(mut self, time0: Num, time1: Num) -> Self {
+        self.time0 = time0;
+        self.time1 = time1;
+        self
+    }
+
     pub fn get_ray<T: Rng>(&self, s: Num, t: Num, rng: &mut T) -> Ray {
         let rd = self.lens_radius * random_in_unit_disk(rng);
         let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
+        let time = if self.time1 > self.time0 {
+            self.time0 + rng.gen::<Num>() * (self.time1 - self.time0)
+        } else {
+            self.time0
+        };
+        Ray::with_time(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
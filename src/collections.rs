@@ -1,5 +1,13 @@
-mod bvh_tree;
+mod bvh_cache;
+pub(crate) mod bvh_tree;
+mod grid;
 mod hitable_vec;
+mod instance;
+mod kd_tree;
 
-pub use bvh_tree::BVHTree;
+pub use bvh_cache::BVHCache;
+pub use bvh_tree::{BVHStats, BVHTree, Refit};
+pub use grid::Grid;
 pub use hitable_vec::HitableVec;
+pub use instance::Instance;
+pub use kd_tree::KdTree;
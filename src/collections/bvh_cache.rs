@@ -0,0 +1,315 @@
+// Numbers are always stored as `f64`, whatever the precision of `Num`.
+#![allow(clippy::unnecessary_cast)]
+
+use crate::bounding_box::BoundingBox;
+use crate::collections::bvh_tree::{BVHNode, BVHTree, MAX_DEPTH};
+use crate::hitable::Hitable;
+use crate::material::Material;
+use crate::num::Num;
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+use rand::prelude::*;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Result, Write};
+
+const MAGIC: &[u8; 8] = b"RTBVH\0\0\x02";
+/// The sizes in bytes of the header, of a node and of a triangle in the file, the last one without
+/// its optional vertex normals and texture coordinates.
+const HEADER_SIZE: u64 = 40;
+const NODE_SIZE: u64 = 56;
+const TRIANGLE_SIZE: u64 = 101;
+
+/// The flags telling which optional attributes follow a triangle in the file.
+const HAS_NORMALS: u8 = 1;
+const HAS_UVS: u8 = 2;
+
+/// What the cache stores of a triangle.
+struct Geometry {
+    normal: Vec3,
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[[Num; 2]; 3]>,
+}
+
+/// A binary cache file holding a prebuilt `BVHTree` over triangles.
+///
+/// The cache is keyed by a hash of the source file and of any parameters used to turn it into
+/// triangles, a cache built from a different source is ignored when loading.
+pub struct BVHCache {
+    path: String,
+    hash: u64,
+}
+
+impl BVHCache {
+    /// Creates a cache stored in `path` for the triangles read from `source` using `params` (the
+    /// scale and offset passed to `stl::read`, for example).
+    pub fn new(path: &str, source: &str, params: &[Num]) -> Result<Self> {
+        let mut hasher = Fnv::default();
+        let mut file = BufReader::new(File::open(source)?);
+        let mut buffer = [0; 1 << 16];
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.write(&buffer[..n]);
+        }
+        for param in params {
+            hasher.write(&(*param as f64).to_le_bytes());
+        }
+
+        Ok(BVHCache {
+            path: path.to_owned(),
+            hash: hasher.0,
+        })
+    }
+
+    /// Loads the tree stored in the cache, giving every triangle a clone of `material`. Returns
+    /// `None` if there is no cache or if it was built from a different source.
+    pub fn load<T: 'static + Material + Clone>(&self, material: T) -> Result<Option<BVHTree>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => BufReader::new(file),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u64(&mut file)? != self.hash {
+            return Ok(None);
+        }
+
+        let n_nodes = read_u64(&mut file)?;
+        let n_triangles = read_u64(&mut file)?;
+        let build_cost = read_num(&mut file)?;
+
+        // The counts come from the file, so they are checked against its length before trusting
+        // them.
+        let remaining = file.get_ref().metadata()?.len().saturating_sub(HEADER_SIZE);
+        let size = n_nodes
+            .checked_mul(NODE_SIZE)
+            .zip(n_triangles.checked_mul(TRIANGLE_SIZE))
+            .and_then(|(nodes, triangles)| nodes.checked_add(triangles));
+        match size {
+            Some(size) if size <= remaining => {}
+            _ => return Err(ErrorKind::InvalidData.into()),
+        }
+        let (n_nodes, n_triangles) = (n_nodes as usize, n_triangles as usize);
+
+        let mut nodes = Vec::new();
+        for _ in 0..n_nodes {
+            let a = read_vec3(&mut file)?;
+            let b = read_vec3(&mut file)?;
+            let offset = read_u32(&mut file)? as usize;
+            let count = read_u32(&mut file)? as usize;
+            let valid = if count > 0 {
+                offset + count <= n_triangles
+            } else {
+                offset > nodes.len() && offset < n_nodes
+            };
+            if !valid {
+                return Err(ErrorKind::InvalidData.into());
+            }
+            nodes.push(BVHNode {
+                bounding_box: BoundingBox::new(a, b),
+                offset,
+                count,
+            });
+        }
+        if nodes.is_empty() || depth(&nodes) > MAX_DEPTH {
+            return Err(ErrorKind::InvalidData.into());
+        }
+
+        let mut indices = Vec::new();
+        let mut primitives = Vec::<Box<dyn Hitable>>::new();
+        for _ in 0..n_triangles {
+            let index = read_u32(&mut file)? as usize;
+            if index >= n_triangles {
+                return Err(ErrorKind::InvalidData.into());
+            }
+            indices.push(index);
+            let flags = read_u8(&mut file)?;
+            let normal = read_vec3(&mut file)?;
+            let p1 = read_vec3(&mut file)?;
+            let p2 = read_vec3(&mut file)?;
+            let p3 = read_vec3(&mut file)?;
+            let mut triangle = Triangle::new(normal, p1, p2, p3, material.clone());
+            if flags & HAS_NORMALS != 0 {
+                triangle = triangle.with_vertex_normals([
+                    read_vec3(&mut file)?,
+                    read_vec3(&mut file)?,
+                    read_vec3(&mut file)?,
+                ]);
+            }
+            if flags & HAS_UVS != 0 {
+                triangle = triangle.with_uvs([
+                    read_uv(&mut file)?,
+                    read_uv(&mut file)?,
+                    read_uv(&mut file)?,
+                ]);
+            }
+            primitives.push(Box::new(triangle));
+        }
+
+        Ok(Some(BVHTree::from_parts(
+            nodes, primitives, indices, build_cost,
+        )))
+    }
+
+    /// Loads the tree stored in the cache, or builds it from the triangles returned by `read`
+    /// and stores it in the cache if the cache is missing, stale or corrupt.
+    pub fn load_or_build<T: 'static + Material + Clone>(
+        &self,
+        material: T,
+        t0: Num,
+        t1: Num,
+        rng: &mut ThreadRng,
+        read: impl FnOnce(T) -> Result<Vec<Triangle<T>>>,
+    ) -> Result<BVHTree> {
+        // Corrupt or truncated caches are rebuilt, other errors are the caller's to handle.
+        match self.load(material.clone()) {
+            Ok(Some(tree)) => return Ok(tree),
+            Ok(None) => {}
+            Err(ref err)
+                if err.kind() == ErrorKind::InvalidData
+                    || err.kind() == ErrorKind::UnexpectedEof => {}
+            Err(err) => return Err(err),
+        }
+
+        let triangles = read(material)?;
+        let geometry = triangles
+            .iter()
+            .map(|triangle| Geometry {
+                normal: triangle.normal(),
+                vertices: triangle.vertices(),
+                normals: triangle.vertex_normals(),
+                uvs: triangle.uvs(),
+            })
+            .collect::<Vec<_>>();
+        let triangles = triangles
+            .into_iter()
+            .map(|triangle| Box::new(triangle) as Box<dyn Hitable>)
+            .collect();
+
+        let tree = BVHTree::new(triangles, t0, t1, rng);
+        self.save(&tree, &geometry)?;
+        Ok(tree)
+    }
+
+    fn save(&self, tree: &BVHTree, geometry: &[Geometry]) -> Result<()> {
+        let mut file = BufWriter::new(File::create(&self.path)?);
+
+        file.write_all(MAGIC)?;
+        file.write_all(&self.hash.to_le_bytes())?;
+        file.write_all(&(tree.nodes().len() as u64).to_le_bytes())?;
+        file.write_all(&(tree.indices().len() as u64).to_le_bytes())?;
+        write_num(&mut file, tree.build_cost())?;
+
+        for node in tree.nodes() {
+            write_vec3(&mut file, node.bounding_box.min())?;
+            write_vec3(&mut file, node.bounding_box.max())?;
+            file.write_all(&(node.offset as u32).to_le_bytes())?;
+            file.write_all(&(node.count as u32).to_le_bytes())?;
+        }
+
+        for &index in tree.indices() {
+            let triangle = &geometry[index];
+            let mut flags = 0;
+            if triangle.normals.is_some() {
+                flags |= HAS_NORMALS;
+            }
+            if triangle.uvs.is_some() {
+                flags |= HAS_UVS;
+            }
+            file.write_all(&(index as u32).to_le_bytes())?;
+            file.write_all(&[flags])?;
+            write_vec3(&mut file, triangle.normal)?;
+            for &p in &triangle.vertices {
+                write_vec3(&mut file, p)?;
+            }
+            for &normal in triangle.normals.iter().flatten() {
+                write_vec3(&mut file, normal)?;
+            }
+            for &[u, v] in triangle.uvs.iter().flatten() {
+                write_num(&mut file, u)?;
+                write_num(&mut file, v)?;
+            }
+        }
+
+        file.flush()
+    }
+}
+
+/// The number of nodes in the longest path from the root to a leaf, which bounds the stack used
+/// to traverse the tree. Children always come after their parent, so the depth of every node is
+/// known once the ones after it are.
+fn depth(nodes: &[BVHNode]) -> usize {
+    let mut depths = vec![1; nodes.len()];
+    for index in (0..nodes.len()).rev() {
+        let node = &nodes[index];
+        if !node.is_leaf() {
+            depths[index] = 1 + depths[index + 1].max(depths[node.offset]);
+        }
+    }
+    depths[0]
+}
+
+/// The 64 bit FNV-1a hash.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn read_u8(file: &mut impl Read) -> Result<u8> {
+    let mut bytes = [0; 1];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32(file: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut impl Read) -> Result<u64> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_num(file: &mut impl Read) -> Result<Num> {
+    Ok(f64::from_bits(read_u64(file)?) as Num)
+}
+
+fn read_vec3(file: &mut impl Read) -> Result<Vec3> {
+    Ok(Vec3::new(read_num(file)?, read_num(file)?, read_num(file)?))
+}
+
+fn read_uv(file: &mut impl Read) -> Result<[Num; 2]> {
+    Ok([read_num(file)?, read_num(file)?])
+}
+
+fn write_num(file: &mut impl Write, x: Num) -> Result<()> {
+    file.write_all(&(x as f64).to_le_bytes())
+}
+
+fn write_vec3(file: &mut impl Write, v: Vec3) -> Result<()> {
+    write_num(file, v.x())?;
+    write_num(file, v.y())?;
+    write_num(file, v.z())
+}
@@ -1,127 +1,440 @@
 use crate::bounding_box::BoundingBox;
 use crate::hitable::*;
-use crate::num::{Int, Num};
+use crate::num::{Int, Maskx4, Num, Numx4, MAX_NUM};
+use crate::packet::{RayPacket, PACKET_SIZE};
 use crate::ray::Ray;
 
 use rand::prelude::*;
 
 use std::cmp::Ordering;
 
+/// Relative cost of visiting a node with respect to intersecting a primitive, used to compute
+/// the surface area heuristic.
+const TRAVERSAL_COST: Num = 1.0;
+/// The deepest tree the traversal stacks can hold, counting the nodes from the root to a leaf.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Default)]
+pub(crate) struct BVHNode {
+    pub bounding_box: BoundingBox,
+    /// For leaves, the index of the first primitive. For interior nodes, the index of the second
+    /// child, the first one is always the next node.
+    pub offset: usize,
+    /// Number of primitives of a leaf, zero for interior nodes.
+    pub count: usize,
+}
+
+impl BVHNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// A bounding volume hierarchy stored as a flat array of nodes in depth-first order.
 pub struct BVHTree {
-    left: Option<Box<dyn Hitable>>,
-    right: Option<Box<dyn Hitable>>,
-    bounding_box: BoundingBox,
+    nodes: Vec<BVHNode>,
+    primitives: Vec<Box<dyn Hitable>>,
+    indices: Vec<usize>,
+    build_cost: Num,
 }
 
 unsafe impl Sync for BVHTree {}
 
+/// Statistics about the shape of a `BVHTree`.
+#[derive(Clone, Debug, Default)]
+pub struct BVHStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub depth: usize,
+    /// The number of leaves holding each amount of primitives, indexed by that amount.
+    pub leaf_sizes: Vec<usize>,
+    /// The surface area heuristic cost, relative to the cost of intersecting a single primitive.
+    pub sah_cost: Num,
+}
+
+/// The outcome of a call to `BVHTree::refit`.
+#[derive(Clone, Copy, Debug)]
+pub struct Refit {
+    pub cost: Num,
+    pub build_cost: Num,
+}
+
+impl Refit {
+    /// The ratio between the current surface area heuristic cost and the one the tree had when
+    /// it was built. Trees built without any area can only degrade if they gain some.
+    pub fn degradation(&self) -> Num {
+        if self.build_cost > 0.0 {
+            self.cost / self.build_cost
+        } else if self.cost > 0.0 {
+            MAX_NUM
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether the tree degraded over `threshold` and should be rebuilt from scratch.
+    pub fn needs_rebuild(&self, threshold: Num) -> bool {
+        self.degradation() > threshold
+    }
+}
+
 impl BVHTree {
-    pub fn new(mut l: Vec<Box<dyn Hitable>>, t0: Num, t1: Num, rng: &mut ThreadRng) -> Self {
-        let n = l.len();
+    pub fn new(l: Vec<Box<dyn Hitable>>, t0: Num, t1: Num, rng: &mut ThreadRng) -> Self {
+        assert!(!l.is_empty());
+
+        let boxes = l
+            .iter()
+            .map(|hitable| hitable.bounding_box(t0, t1).unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        let (nodes, indices) = build(&boxes, rng);
 
-        match (3.0 * rng.gen::<Num>()) as Int {
-            0 => l.sort_by(cmp_x),
-            1 => l.sort_by(cmp_y),
-            _ => l.sort_by(cmp_z),
+        let mut l = l.into_iter().map(Some).collect::<Vec<_>>();
+        let primitives = indices
+            .iter()
+            .map(|&index| l[index].take().unwrap())
+            .collect();
+
+        let build_cost = sah_cost(&nodes);
+
+        BVHTree {
+            nodes,
+            primitives,
+            indices,
+            build_cost,
+        }
+    }
+
+    pub(crate) fn from_parts(
+        nodes: Vec<BVHNode>,
+        primitives: Vec<Box<dyn Hitable>>,
+        indices: Vec<usize>,
+        build_cost: Num,
+    ) -> Self {
+        BVHTree {
+            nodes,
+            primitives,
+            indices,
+            build_cost,
+        }
+    }
+
+    pub(crate) fn nodes(&self) -> &[BVHNode] {
+        &self.nodes
+    }
+
+    pub(crate) fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub(crate) fn build_cost(&self) -> Num {
+        self.build_cost
+    }
+
+    pub fn stats(&self) -> BVHStats {
+        let mut stats = BVHStats {
+            node_count: self.nodes.len(),
+            sah_cost: sah_cost(&self.nodes),
+            ..Default::default()
         };
 
-        match n {
-            0 => unreachable!(),
-            1 => {
-                let left = l.pop().unwrap();
-                let bounding_box = left.bounding_box(t0, t1).unwrap_or_default();
-                BVHTree {
-                    left: Some(left),
-                    right: None,
-                    bounding_box,
-                }
-            }
-            2 => {
-                let left = l.pop().unwrap();
-                let right = l.pop().unwrap();
-                let box_left = left.bounding_box(t0, t1).unwrap_or_default();
-                let box_right = right.bounding_box(t0, t1).unwrap_or_default();
-                let bounding_box = box_left.surrounding_box(&box_right);
-                BVHTree {
-                    left: Some(left),
-                    right: Some(right),
-                    bounding_box,
-                }
-            }
-            _ => {
-                let l_right = l.split_off(n / 2);
-                let left = Self::new(l, t0, t1, rng);
-                let right = Self::new(l_right, t0, t1, rng);
-                let box_left = left.bounding_box(t0, t1).unwrap_or_default();
-                let box_right = right.bounding_box(t0, t1).unwrap_or_default();
-                let bounding_box = box_left.surrounding_box(&box_right);
-                BVHTree {
-                    left: Some(Box::new(left)),
-                    right: Some(Box::new(right)),
-                    bounding_box,
+        let mut stack = vec![(0, 1)];
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.nodes[index];
+            stats.depth = stats.depth.max(depth);
+            if node.is_leaf() {
+                stats.leaf_count += 1;
+                if stats.leaf_sizes.len() <= node.count {
+                    stats.leaf_sizes.resize(node.count + 1, 0);
                 }
+                stats.leaf_sizes[node.count] += 1;
+            } else {
+                stack.push((index + 1, depth + 1));
+                stack.push((node.offset, depth + 1));
             }
         }
+
+        stats
+    }
+
+    /// Iterates over the primitives of the tree alongside their position in the vector used to
+    /// build it, so they can be moved or replaced before calling `refit`.
+    pub fn primitives_mut(&mut self) -> impl Iterator<Item = (usize, &mut Box<dyn Hitable>)> {
+        self.indices.iter().cloned().zip(self.primitives.iter_mut())
+    }
+
+    /// Recomputes the bounding boxes of every node after the primitives moved, keeping the
+    /// topology of the tree.
+    pub fn refit(&mut self, t0: Num, t1: Num) -> Refit {
+        for i in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[i];
+            let bounding_box = if node.is_leaf() {
+                let leaf = &self.primitives[node.offset..node.offset + node.count];
+                leaf.iter()
+                    .map(|hitable| hitable.bounding_box(t0, t1).unwrap_or_default())
+                    .fold(None, |acc: Option<BoundingBox>, bounding_box| match acc {
+                        Some(acc) => Some(acc.surrounding_box(&bounding_box)),
+                        None => Some(bounding_box),
+                    })
+                    .unwrap_or_default()
+            } else {
+                let left = &self.nodes[i + 1].bounding_box;
+                let right = &self.nodes[node.offset].bounding_box;
+                left.surrounding_box(right)
+            };
+            self.nodes[i].bounding_box = bounding_box;
+        }
+
+        Refit {
+            cost: sah_cost(&self.nodes),
+            build_cost: self.build_cost,
+        }
     }
 }
 
 impl Hitable for BVHTree {
     fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
-        if self.bounding_box.hit(r, t_min, t_max, rec) {
-            if {
-                match &self.left {
-                    Some(left) => left.hit(r, t_min, t_max, rec),
-                    None => false,
+        let mut hit_anything = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        traverse(
+            &self.nodes,
+            0,
+            r,
+            t_min,
+            &mut closest,
+            &mut stats,
+            |i, closest, _| {
+                if self.primitives[i].hit(r, t_min, *closest, rec) {
+                    hit_anything = true;
+                    *closest = rec.t;
                 }
-            } {
-                if let Some(right) = &self.right {
-                    right.hit(r, t_min, rec.t, rec);
+                false
+            },
+        );
+        hit_anything
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        let mut occluded = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        traverse(
+            &self.nodes,
+            0,
+            r,
+            t_min,
+            &mut closest,
+            &mut stats,
+            |i, closest, _| {
+                occluded = self.primitives[i].occluded(r, t_min, *closest);
+                occluded
+            },
+        );
+        occluded
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let mut hit_anything = false;
+        let mut closest = t_max;
+        traverse(
+            &self.nodes,
+            0,
+            r,
+            t_min,
+            &mut closest,
+            stats,
+            |i, closest, stats| {
+                if self.primitives[i].hit_counting(r, t_min, *closest, rec, stats) {
+                    hit_anything = true;
+                    *closest = rec.t;
                 }
-                true
-            } else {
-                match &self.right {
-                    Some(right) => right.hit(r, t_min, t_max, rec),
-                    None => false,
+                false
+            },
+        );
+        hit_anything
+    }
+
+    fn hit_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        t_min: Num,
+        t_max: &mut Numx4,
+        active: Maskx4,
+        recs: &mut [HitRecord<'a>; PACKET_SIZE],
+    ) -> Maskx4 {
+        let mut hit = Maskx4::splat(false);
+        let mut stack = [(0, active); MAX_DEPTH];
+        let mut len = 1;
+
+        while len > 0 {
+            len -= 1;
+            let (index, mask) = stack[len];
+            let node = &self.nodes[index];
+            let mask = mask & node.bounding_box.hit_packet(packet, t_min, *t_max);
+
+            if mask.none() {
+                continue;
+            }
+
+            if mask.bitmask().count_ones() == 1 {
+                // The packet diverged, finish this subtree with a single ray.
+                let lane = mask.bitmask().trailing_zeros() as usize;
+                let r = packet.ray(lane);
+                let rec = &mut recs[lane];
+                let mut closest = t_max.extract(lane);
+                let mut stats = TraversalStats::default();
+                traverse(
+                    &self.nodes,
+                    index,
+                    r,
+                    t_min,
+                    &mut closest,
+                    &mut stats,
+                    |i, closest, _| {
+                        if self.primitives[i].hit(r, t_min, *closest, rec) {
+                            hit = hit.replace(lane, true);
+                            *closest = rec.t;
+                        }
+                        false
+                    },
+                );
+                *t_max = t_max.replace(lane, closest);
+            } else if node.is_leaf() {
+                for primitive in &self.primitives[node.offset..node.offset + node.count] {
+                    hit |= primitive.hit_packet(packet, t_min, t_max, mask, recs);
                 }
+            } else {
+                stack[len] = (node.offset, mask);
+                stack[len + 1] = (index + 1, mask);
+                len += 2;
             }
-        } else {
-            false
         }
+
+        hit
     }
 
     fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
-        Some(self.bounding_box.clone())
+        Some(self.nodes[0].bounding_box.clone())
     }
 }
 
-fn cmp_x(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
-    let box_left = a.bounding_box(0.0, 0.0).unwrap_or_default();
-    let box_right = b.bounding_box(0.0, 0.0).unwrap_or_default();
-    box_left
-        .min()
-        .x()
-        .partial_cmp(&box_right.min().x())
-        .unwrap_or(Ordering::Equal)
+/// Visits every primitive whose leaf, in the subtree starting at the `root` node, is hit by `r`
+/// between `t_min` and `closest`. The callback
+/// may shrink `closest` to prune the remaining nodes, or return `true` to stop the traversal.
+/// Every node tested is counted in `stats`.
+pub(crate) fn traverse(
+    nodes: &[BVHNode],
+    root: usize,
+    r: &Ray,
+    t_min: Num,
+    closest: &mut Num,
+    stats: &mut TraversalStats,
+    mut f: impl FnMut(usize, &mut Num, &mut TraversalStats) -> bool,
+) {
+    let mut stack = [root; MAX_DEPTH];
+    let mut len = 1;
+    let mut rec = HitRecord::default();
+
+    while len > 0 {
+        len -= 1;
+        let index = stack[len];
+        let node = &nodes[index];
+        stats.node_tests += 1;
+        if node.bounding_box.hit(r, t_min, *closest, &mut rec) {
+            if node.is_leaf() {
+                for i in node.offset..node.offset + node.count {
+                    if f(i, closest, stats) {
+                        return;
+                    }
+                }
+            } else {
+                stack[len] = node.offset;
+                stack[len + 1] = index + 1;
+                len += 2;
+            }
+        }
+    }
 }
 
-fn cmp_y(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
-    let box_left = a.bounding_box(0.0, 0.0).unwrap_or_default();
-    let box_right = b.bounding_box(0.0, 0.0).unwrap_or_default();
+/// Builds the nodes of a tree over primitives with the given bounding boxes. Returns the nodes
+/// and the permutation of the primitives in the order the leaves reference them.
+pub(crate) fn build(boxes: &[BoundingBox], rng: &mut impl Rng) -> (Vec<BVHNode>, Vec<usize>) {
+    let mut indices = (0..boxes.len()).collect::<Vec<_>>();
+    let mut nodes = Vec::with_capacity(2 * boxes.len());
+    build_node(&mut nodes, &mut indices, 0, boxes, rng);
+    (nodes, indices)
+}
+
+fn build_node(
+    nodes: &mut Vec<BVHNode>,
+    indices: &mut [usize],
+    offset: usize,
+    boxes: &[BoundingBox],
+    rng: &mut impl Rng,
+) -> usize {
+    let n = indices.len();
+    let index = nodes.len();
+
+    match (3.0 * rng.gen::<Num>()) as Int {
+        0 => indices.sort_by(|&a, &b| cmp(boxes[a].min().x(), boxes[b].min().x())),
+        1 => indices.sort_by(|&a, &b| cmp(boxes[a].min().y(), boxes[b].min().y())),
+        _ => indices.sort_by(|&a, &b| cmp(boxes[a].min().z(), boxes[b].min().z())),
+    };
 
-    box_left
-        .min()
-        .y()
-        .partial_cmp(&box_right.min().y())
-        .unwrap_or(Ordering::Equal)
+    let bounding_box = indices[1..]
+        .iter()
+        .fold(boxes[indices[0]].clone(), |acc, &i| {
+            acc.surrounding_box(&boxes[i])
+        });
+
+    if n <= 2 {
+        nodes.push(BVHNode {
+            bounding_box,
+            offset,
+            count: n,
+        });
+    } else {
+        nodes.push(BVHNode {
+            bounding_box,
+            offset: 0,
+            count: 0,
+        });
+        let (left, right) = indices.split_at_mut(n / 2);
+        build_node(nodes, left, offset, boxes, rng);
+        nodes[index].offset = build_node(nodes, right, offset + n / 2, boxes, rng);
+    }
+
+    index
 }
 
-fn cmp_z(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
-    let box_left = a.bounding_box(0.0, 0.0).unwrap_or_default();
-    let box_right = b.bounding_box(0.0, 0.0).unwrap_or_default();
+fn cmp(a: Num, b: Num) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
 
-    box_left
-        .min()
-        .z()
-        .partial_cmp(&box_right.min().z())
-        .unwrap_or(Ordering::Equal)
+/// The surface area heuristic cost of a tree, relative to the cost of intersecting a single
+/// primitive.
+pub(crate) fn sah_cost(nodes: &[BVHNode]) -> Num {
+    let root_area = nodes[0].bounding_box.area();
+    if root_area <= 0.0 {
+        return 0.0;
+    }
+    nodes
+        .iter()
+        .map(|node| {
+            let cost = if node.is_leaf() {
+                node.count as Num
+            } else {
+                TRAVERSAL_COST
+            };
+            cost * node.bounding_box.area() / root_area
+        })
+        .sum()
 }
@@ -0,0 +1,296 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::*;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+const MAX_RESOLUTION: usize = 64;
+/// Cells with more primitives than this are subdivided by a nested grid.
+const MAX_CELL_SIZE: usize = 16;
+const MAX_LEVELS: usize = 3;
+
+enum Cell {
+    Primitives { offset: usize, count: usize },
+    Grid(Box<Level>),
+}
+
+struct Level {
+    bounding_box: BoundingBox,
+    resolution: [usize; 3],
+    cell_size: Vec3,
+    cells: Vec<Cell>,
+}
+
+/// A uniform grid over the primitives traversed with a 3D DDA.
+///
+/// When `hierarchical` is set, crowded cells are split further by nested grids, which copes
+/// better with scenes where the primitives are not evenly distributed.
+pub struct Grid {
+    root: Level,
+    primitives: Vec<Box<dyn Hitable>>,
+    indices: Vec<usize>,
+}
+
+unsafe impl Sync for Grid {}
+
+impl Grid {
+    pub fn new(l: Vec<Box<dyn Hitable>>, t0: Num, t1: Num, hierarchical: bool) -> Self {
+        assert!(!l.is_empty());
+
+        let boxes = l
+            .iter()
+            .map(|hitable| hitable.bounding_box(t0, t1).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let bounding_box = boxes[1..]
+            .iter()
+            .fold(boxes[0].clone(), |acc, b| acc.surrounding_box(b));
+
+        let max_levels = if hierarchical { MAX_LEVELS } else { 1 };
+        let mut indices = Vec::new();
+        let primitives = (0..boxes.len()).collect::<Vec<_>>();
+        let root = Level::new(&boxes, &primitives, bounding_box, max_levels, &mut indices);
+
+        Grid {
+            root,
+            primitives: l,
+            indices,
+        }
+    }
+
+    fn traverse(
+        &self,
+        r: &Ray,
+        t_min: Num,
+        closest: &mut Num,
+        stats: &mut TraversalStats,
+        mut f: impl FnMut(usize, &mut Num, &mut TraversalStats) -> bool,
+    ) {
+        if let Some((t0, t1)) = self.root.bounding_box.intersect(r, t_min, *closest) {
+            self.root
+                .traverse(&self.indices, r, t0, t1, closest, stats, &mut f);
+        }
+    }
+}
+
+impl Level {
+    fn new(
+        boxes: &[BoundingBox],
+        primitives: &[usize],
+        bounding_box: BoundingBox,
+        levels: usize,
+        indices: &mut Vec<usize>,
+    ) -> Self {
+        let diagonal = bounding_box.max() - bounding_box.min();
+        let max_extent = max(diagonal.x(), max(diagonal.y(), diagonal.z()));
+        let cells_per_unit = if max_extent > 0.0 {
+            3.0 * (primitives.len() as Num).cbrt() / max_extent
+        } else {
+            0.0
+        };
+
+        let mut resolution = [1; 3];
+        for (axis, res) in resolution.iter_mut().enumerate() {
+            let cells = (diagonal.get(axis) * cells_per_unit).round() as usize;
+            *res = cells.clamp(1, MAX_RESOLUTION);
+        }
+        let cell_size = diagonal
+            / Vec3::new(
+                resolution[0] as Num,
+                resolution[1] as Num,
+                resolution[2] as Num,
+            );
+
+        let mut level = Level {
+            bounding_box,
+            resolution,
+            cell_size,
+            cells: Vec::new(),
+        };
+
+        let mut contents = vec![Vec::new(); resolution[0] * resolution[1] * resolution[2]];
+        for &i in primitives {
+            let lo = level.cell_of(boxes[i].min());
+            let hi = level.cell_of(boxes[i].max());
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        contents[level.cell_index([x, y, z])].push(i);
+                    }
+                }
+            }
+        }
+
+        let subdivide = levels > 1 && contents.len() > 1;
+        for (cell, content) in contents.into_iter().enumerate() {
+            if subdivide && content.len() > MAX_CELL_SIZE {
+                let bounds = level.cell_bounds(cell);
+                let grid = Level::new(boxes, &content, bounds, levels - 1, indices);
+                level.cells.push(Cell::Grid(Box::new(grid)));
+            } else {
+                level.cells.push(Cell::Primitives {
+                    offset: indices.len(),
+                    count: content.len(),
+                });
+                indices.extend(content);
+            }
+        }
+
+        level
+    }
+
+    fn cell_of(&self, p: Vec3) -> [usize; 3] {
+        let mut cell = [0; 3];
+        let rel = p - self.bounding_box.min();
+        for (axis, c) in cell.iter_mut().enumerate() {
+            let size = self.cell_size.get(axis);
+            let i = if size > 0.0 {
+                (rel.get(axis) / size).floor()
+            } else {
+                0.0
+            };
+            *c = max(0.0, min(i, (self.resolution[axis] - 1) as Num)) as usize;
+        }
+        cell
+    }
+
+    fn cell_index(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.resolution[1] + cell[1]) * self.resolution[0] + cell[0]
+    }
+
+    fn cell_bounds(&self, index: usize) -> BoundingBox {
+        let x = index % self.resolution[0];
+        let y = (index / self.resolution[0]) % self.resolution[1];
+        let z = index / (self.resolution[0] * self.resolution[1]);
+        let lo = self.bounding_box.min() + Vec3::new(x as Num, y as Num, z as Num) * self.cell_size;
+        BoundingBox::new(lo, lo + self.cell_size)
+    }
+
+    /// Walks the cells crossed by `r` between `t0` and `t1` in order. Returns `true` if the
+    /// traversal was stopped by the callback.
+    #[allow(clippy::too_many_arguments)]
+    fn traverse(
+        &self,
+        indices: &[usize],
+        r: &Ray,
+        t0: Num,
+        t1: Num,
+        closest: &mut Num,
+        stats: &mut TraversalStats,
+        f: &mut impl FnMut(usize, &mut Num, &mut TraversalStats) -> bool,
+    ) -> bool {
+        let origin = r.origin();
+        let direction = r.direction();
+        let start = self.cell_of(r.point_at(t0));
+
+        let mut cell = [0isize; 3];
+        let mut step = [0isize; 3];
+        let mut out = [0isize; 3];
+        let mut next = [MAX_NUM; 3];
+        let mut delta = [MAX_NUM; 3];
+        for axis in 0..3 {
+            cell[axis] = start[axis] as isize;
+            let d = direction.get(axis);
+            let size = self.cell_size.get(axis);
+            let lo = self.bounding_box.min().get(axis);
+            if d > 0.0 {
+                let boundary = lo + (start[axis] + 1) as Num * size;
+                next[axis] = (boundary - origin.get(axis)) / d;
+                delta[axis] = size / d;
+                step[axis] = 1;
+                out[axis] = self.resolution[axis] as isize;
+            } else if d < 0.0 {
+                let boundary = lo + start[axis] as Num * size;
+                next[axis] = (boundary - origin.get(axis)) / d;
+                delta[axis] = -size / d;
+                step[axis] = -1;
+                out[axis] = -1;
+            }
+        }
+
+        let mut t_enter = t0;
+        loop {
+            let axis = if next[0] < next[1] {
+                if next[0] < next[2] {
+                    0
+                } else {
+                    2
+                }
+            } else if next[1] < next[2] {
+                1
+            } else {
+                2
+            };
+            let t_exit = min(next[axis], t1);
+
+            stats.node_tests += 1;
+            let index = self.cell_index([cell[0] as usize, cell[1] as usize, cell[2] as usize]);
+            match &self.cells[index] {
+                Cell::Primitives { offset, count } => {
+                    for &i in &indices[*offset..*offset + *count] {
+                        if f(i, closest, stats) {
+                            return true;
+                        }
+                    }
+                }
+                Cell::Grid(grid) => {
+                    if grid.traverse(indices, r, t_enter, t_exit, closest, stats, f) {
+                        return true;
+                    }
+                }
+            }
+
+            // Cells are visited front to back so no later hit can be closer.
+            if *closest <= t_exit || next[axis] >= t1 {
+                return false;
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] == out[axis] {
+                return false;
+            }
+            t_enter = next[axis];
+            next[axis] += delta[axis];
+        }
+    }
+}
+
+impl Hitable for Grid {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.hit_counting(r, t_min, t_max, rec, &mut TraversalStats::default())
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        let mut occluded = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        self.traverse(r, t_min, &mut closest, &mut stats, |i, closest, _| {
+            occluded = self.primitives[i].occluded(r, t_min, *closest);
+            occluded
+        });
+        occluded
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let mut hit_anything = false;
+        let mut closest = t_max;
+        self.traverse(r, t_min, &mut closest, stats, |i, closest, stats| {
+            if self.primitives[i].hit_counting(r, t_min, *closest, rec, stats) {
+                hit_anything = true;
+                *closest = rec.t;
+            }
+            false
+        });
+        hit_anything
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(self.root.bounding_box.clone())
+    }
+}
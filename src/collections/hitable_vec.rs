@@ -1,6 +1,7 @@
 use crate::bounding_box::BoundingBox;
 use crate::hitable::*;
-use crate::num::Num;
+use crate::num::{Maskx4, Num, Numx4};
+use crate::packet::{RayPacket, PACKET_SIZE};
 use crate::ray::Ray;
 
 #[derive(Default)]
@@ -35,6 +36,50 @@ impl Hitable for HitableVec {
         hit_anything
     }
 
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.inner
+            .iter()
+            .any(|hitable| hitable.occluded(r, t_min, t_max))
+    }
+
+    fn hit_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        t_min: Num,
+        t_max: &mut Numx4,
+        active: Maskx4,
+        recs: &mut [HitRecord<'a>; PACKET_SIZE],
+    ) -> Maskx4 {
+        let mut hit = Maskx4::splat(false);
+        for hitable in &self.inner {
+            hit |= hitable.hit_packet(packet, t_min, t_max, active, recs);
+        }
+        hit
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let mut temp_rec = HitRecord::default();
+        let mut hit_anything = false;
+        let mut closest = t_max;
+        for hitable in &self.inner {
+            if hitable.hit_counting(r, t_min, closest, &mut temp_rec, stats) {
+                hit_anything = true;
+                closest = temp_rec.t;
+            }
+        }
+        if hit_anything {
+            *rec = temp_rec;
+        }
+        hit_anything
+    }
+
     fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
         let mut hitables = self.inner.iter();
         let mut temp_box = hitables.next()?.bounding_box(t0, t1)?;
@@ -0,0 +1,122 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::*;
+use crate::num::Num;
+use crate::ray::Ray;
+use crate::transform::{AnimatedTransform, Transform};
+use crate::transformed;
+
+use std::sync::Arc;
+
+/// A placement of a shared object in the world.
+///
+/// Every instance holds a reference counted pointer to its object, usually a
+/// `BVHTree` built once per unique mesh, so placing the same mesh many times
+/// only costs a transform per copy. A `BVHTree` over instances acts as the
+/// top level of a two-level acceleration structure.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hitable>,
+    transform: Transform,
+    inverse: Transform,
+    motion: Option<AnimatedTransform>,
+    id: Option<usize>,
+}
+
+unsafe impl Sync for Instance {}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hitable>, transform: Transform) -> Self {
+        let inverse = transform.inverse();
+        Instance {
+            object,
+            transform,
+            inverse,
+            motion: None,
+            id: None,
+        }
+    }
+
+    /// An instance moving along `motion`, placed according to the time of each ray.
+    pub fn animated(object: Arc<dyn Hitable>, motion: AnimatedTransform) -> Self {
+        let mut instance = Self::new(object, motion.start().clone());
+        if motion.is_animated() {
+            instance.motion = Some(motion);
+        }
+        instance
+    }
+
+    /// Sets the id reported as `instance_id` in the hit records of this instance.
+    pub fn with_id(mut self, id: usize) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// The transform of the instance, or where its motion starts for animated instances.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Calls `f` with the transform at `time` and its inverse.
+    fn at<R>(&self, time: Num, f: impl FnOnce(&Transform, &Transform) -> R) -> R {
+        match &self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+                f(&transform, &transform.inverse())
+            }
+            None => f(&self.transform, &self.inverse),
+        }
+    }
+
+    fn to_world(&self, transform: &Transform, rec: &mut HitRecord) {
+        transformed::to_world(transform, rec);
+        if self.id.is_some() {
+            rec.instance_id = self.id;
+        }
+    }
+}
+
+impl Hitable for Instance {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.at(r.time(), |transform, inverse| {
+            if self.object.hit(&inverse.ray(r), t_min, t_max, rec) {
+                self.to_world(transform, rec);
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.at(r.time(), |_, inverse| {
+            self.object.occluded(&inverse.ray(r), t_min, t_max)
+        })
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        self.at(r.time(), |transform, inverse| {
+            let local = inverse.ray(r);
+            if self.object.hit_counting(&local, t_min, t_max, rec, stats) {
+                self.to_world(transform, rec);
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        let bounding_box = self.object.bounding_box(t0, t1)?;
+        match &self.motion {
+            Some(motion) => Some(motion.bounding_box(&bounding_box, t0, t1)),
+            None => Some(self.transform.bounding_box(&bounding_box)),
+        }
+    }
+}
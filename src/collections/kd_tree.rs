@@ -0,0 +1,332 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::*;
+use crate::num::Num;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::cmp::Ordering;
+
+const INTERSECTION_COST: Num = 80.0;
+const TRAVERSAL_COST: Num = 1.0;
+const EMPTY_BONUS: Num = 0.5;
+const MAX_LEAF_SIZE: usize = 1;
+const MAX_DEPTH: usize = 64;
+
+struct KdNode {
+    split: Num,
+    /// The splitting axis, or 3 for leaves.
+    axis: usize,
+    /// For leaves, the index of the first primitive index. For interior nodes, the index of the
+    /// child above the split, the child below it is always the next node.
+    offset: usize,
+    count: usize,
+}
+
+impl KdNode {
+    fn is_leaf(&self) -> bool {
+        self.axis == 3
+    }
+}
+
+/// A kd-tree with split planes chosen using the surface area heuristic.
+///
+/// Unlike a `BVHTree`, the space is split in disjoint regions and primitives crossing a split
+/// plane are referenced from both sides of it.
+pub struct KdTree {
+    nodes: Vec<KdNode>,
+    primitives: Vec<Box<dyn Hitable>>,
+    indices: Vec<usize>,
+    bounding_box: BoundingBox,
+}
+
+unsafe impl Sync for KdTree {}
+
+#[derive(Clone, Copy)]
+struct Edge {
+    t: Num,
+    primitive: usize,
+    start: bool,
+}
+
+impl KdTree {
+    pub fn new(l: Vec<Box<dyn Hitable>>, t0: Num, t1: Num) -> Self {
+        assert!(!l.is_empty());
+
+        let boxes = l
+            .iter()
+            .map(|hitable| hitable.bounding_box(t0, t1).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let bounding_box = boxes[1..]
+            .iter()
+            .fold(boxes[0].clone(), |acc, b| acc.surrounding_box(b));
+
+        let mut tree = KdTree {
+            nodes: Vec::new(),
+            primitives: l,
+            indices: Vec::new(),
+            bounding_box: bounding_box.clone(),
+        };
+
+        let max_depth = (8.0 + 1.3 * (boxes.len() as Num).log2()).round() as usize;
+        let primitives = (0..boxes.len()).collect();
+        tree.build(
+            &boxes,
+            primitives,
+            bounding_box,
+            max_depth.min(MAX_DEPTH - 1),
+        );
+        tree
+    }
+
+    fn build(
+        &mut self,
+        boxes: &[BoundingBox],
+        primitives: Vec<usize>,
+        bounds: BoundingBox,
+        depth: usize,
+    ) {
+        let n = primitives.len();
+        let index = self.nodes.len();
+
+        let split = if n <= MAX_LEAF_SIZE || depth == 0 {
+            None
+        } else {
+            best_split(boxes, &primitives, &bounds)
+        };
+
+        match split {
+            None => {
+                self.nodes.push(KdNode {
+                    split: 0.0,
+                    axis: 3,
+                    offset: self.indices.len(),
+                    count: n,
+                });
+                self.indices.extend(primitives);
+            }
+            Some((axis, split)) => {
+                let below = primitives
+                    .iter()
+                    .cloned()
+                    .filter(|&i| boxes[i].min().get(axis) <= split)
+                    .collect();
+                let above = primitives
+                    .into_iter()
+                    .filter(|&i| boxes[i].max().get(axis) >= split)
+                    .collect();
+
+                self.nodes.push(KdNode {
+                    split,
+                    axis,
+                    offset: 0,
+                    count: 0,
+                });
+
+                let bounds_below = BoundingBox::new(bounds.min(), with(bounds.max(), axis, split));
+                let bounds_above = BoundingBox::new(with(bounds.min(), axis, split), bounds.max());
+
+                self.build(boxes, below, bounds_below, depth - 1);
+                self.nodes[index].offset = self.nodes.len();
+                self.build(boxes, above, bounds_above, depth - 1);
+            }
+        }
+    }
+
+    /// Visits every primitive in the leaves crossed by `r`, from front to back. The callback may
+    /// shrink `closest` or return `true` to stop the traversal.
+    fn traverse(
+        &self,
+        r: &Ray,
+        t_min: Num,
+        closest: &mut Num,
+        stats: &mut TraversalStats,
+        mut f: impl FnMut(usize, &mut Num, &mut TraversalStats) -> bool,
+    ) {
+        let (t0, t1) = match self.bounding_box.intersect(r, t_min, *closest) {
+            Some(range) => range,
+            None => return,
+        };
+
+        let mut stack = [(0, 0.0, 0.0); MAX_DEPTH];
+        let mut len = 0;
+        let (mut index, mut t0, mut t1) = (0, t0, t1);
+
+        loop {
+            if t0 <= *closest {
+                let node = &self.nodes[index];
+                stats.node_tests += 1;
+                if node.is_leaf() {
+                    for &i in &self.indices[node.offset..node.offset + node.count] {
+                        if f(i, closest, stats) {
+                            return;
+                        }
+                    }
+                    // Nodes are visited front to back so no later hit can be closer.
+                    if *closest <= t1 {
+                        return;
+                    }
+                } else {
+                    let origin = r.origin().get(node.axis);
+                    let direction = r.direction().get(node.axis);
+                    let t_split = (node.split - origin) / direction;
+
+                    let (near, far) =
+                        if origin < node.split || (origin == node.split && direction <= 0.0) {
+                            (index + 1, node.offset)
+                        } else {
+                            (node.offset, index + 1)
+                        };
+
+                    if t_split > t1 || t_split <= 0.0 {
+                        index = near;
+                    } else if t_split < t0 {
+                        index = far;
+                    } else {
+                        stack[len] = (far, t_split, t1);
+                        len += 1;
+                        index = near;
+                        t1 = t_split;
+                    }
+                    continue;
+                }
+            }
+
+            if len == 0 {
+                return;
+            }
+            len -= 1;
+            let next = stack[len];
+            index = next.0;
+            t0 = next.1;
+            t1 = next.2;
+        }
+    }
+}
+
+impl Hitable for KdTree {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.hit_counting(r, t_min, t_max, rec, &mut TraversalStats::default())
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        let mut occluded = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        self.traverse(r, t_min, &mut closest, &mut stats, |i, closest, _| {
+            occluded = self.primitives[i].occluded(r, t_min, *closest);
+            occluded
+        });
+        occluded
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let mut hit_anything = false;
+        let mut closest = t_max;
+        self.traverse(r, t_min, &mut closest, stats, |i, closest, stats| {
+            if self.primitives[i].hit_counting(r, t_min, *closest, rec, stats) {
+                hit_anything = true;
+                *closest = rec.t;
+            }
+            false
+        });
+        hit_anything
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(self.bounding_box.clone())
+    }
+}
+
+/// Finds the split plane with the lowest surface area heuristic cost, if splitting is cheaper
+/// than intersecting every primitive.
+fn best_split(
+    boxes: &[BoundingBox],
+    primitives: &[usize],
+    bounds: &BoundingBox,
+) -> Option<(usize, Num)> {
+    let n = primitives.len();
+    let total_area = bounds.area();
+    if total_area <= 0.0 {
+        return None;
+    }
+    let inv_area = 1.0 / total_area;
+    let diagonal = bounds.max() - bounds.min();
+
+    let mut best_cost = INTERSECTION_COST * n as Num;
+    let mut best = None;
+    let mut edges = Vec::with_capacity(2 * n);
+
+    for axis in 0..3 {
+        edges.clear();
+        for &i in primitives {
+            edges.push(Edge {
+                t: boxes[i].min().get(axis),
+                primitive: i,
+                start: true,
+            });
+            edges.push(Edge {
+                t: boxes[i].max().get(axis),
+                primitive: i,
+                start: false,
+            });
+        }
+        edges.sort_by(|a, b| {
+            a.t.partial_cmp(&b.t)
+                .unwrap_or(Ordering::Equal)
+                .then(b.start.cmp(&a.start))
+                .then(a.primitive.cmp(&b.primitive))
+        });
+
+        let lo = bounds.min().get(axis);
+        let hi = bounds.max().get(axis);
+        let (other0, other1) = ((axis + 1) % 3, (axis + 2) % 3);
+        let d0 = diagonal.get(other0);
+        let d1 = diagonal.get(other1);
+
+        let mut n_below = 0;
+        let mut n_above = n;
+        for edge in &edges {
+            if !edge.start {
+                n_above -= 1;
+            }
+            if edge.t > lo && edge.t < hi {
+                let area_below = 2.0 * (d0 * d1 + (edge.t - lo) * (d0 + d1));
+                let area_above = 2.0 * (d0 * d1 + (hi - edge.t) * (d0 + d1));
+                let bonus = if n_below == 0 || n_above == 0 {
+                    EMPTY_BONUS
+                } else {
+                    0.0
+                };
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (1.0 - bonus)
+                        * inv_area
+                        * (area_below * n_below as Num + area_above * n_above as Num);
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, edge.t));
+                }
+            }
+            if edge.start {
+                n_below += 1;
+            }
+        }
+    }
+
+    best
+}
+
+fn with(v: Vec3, axis: usize, value: Num) -> Vec3 {
+    match axis {
+        0 => Vec3::new(value, v.y(), v.z()),
+        1 => Vec3::new(v.x(), value, v.z()),
+        _ => Vec3::new(v.x(), v.y(), value),
+    }
+}
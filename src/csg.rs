@@ -0,0 +1,250 @@
+use crate::bounding_box::BoundingBox;
+use crate::cuboid::Cuboid;
+use crate::hitable::{HitRecord, Hitable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::num::*;
+use crate::quadric::CappedQuadric;
+use crate::ray::Ray;
+use crate::sdf::{Sdf, SdfShape};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::torus::Torus;
+use crate::triangle_mesh::ClosedMesh;
+
+use std::iter;
+
+/// A stretch of a ray inside a solid, with the hits where the ray enters and leaves it. They are
+/// `None` when the ray is already inside at the start of the query or still inside at its end.
+#[derive(Clone)]
+pub struct Span<'a> {
+    pub interval: Interval,
+    pub enter: Option<HitRecord<'a>>,
+    pub exit: Option<HitRecord<'a>>,
+}
+
+/// A closed surface, splitting space between its inside and its outside. Rays hit its outer side
+/// when entering it, so `front_face` tells entries from exits.
+pub trait Solid: Hitable {
+    /// Appends the spans of `r` inside the solid between `t_min` and `t_max`, in order. The
+    /// default walks the hits along the ray one by one.
+    fn spans<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, spans: &mut Vec<Span<'a>>) {
+        let mut t = t_min;
+        let mut enter: Option<(Num, Option<HitRecord<'a>>)> = None;
+        let mut first = true;
+        loop {
+            let mut rec = HitRecord::default();
+            if !self.hit(r, t, t_max, &mut rec) {
+                break;
+            }
+            t = rec.t;
+            if rec.front_face {
+                // Rounding errors may give two entries in a row, the first one is kept.
+                if enter.is_none() {
+                    enter = Some((t, Some(rec)));
+                }
+            } else if let Some((start, rec_enter)) = enter.take() {
+                spans.push(Span {
+                    interval: Interval::new(start, t),
+                    enter: rec_enter,
+                    exit: Some(rec),
+                });
+            } else if first {
+                // Leaving before entering, the ray starts inside.
+                spans.push(Span {
+                    interval: Interval::new(t_min, t),
+                    enter: None,
+                    exit: Some(rec),
+                });
+            }
+            first = false;
+        }
+
+        if let Some((start, rec_enter)) = enter {
+            spans.push(Span {
+                interval: Interval::new(start, t_max),
+                enter: rec_enter,
+                exit: None,
+            });
+        }
+    }
+}
+
+impl<T: Material> Solid for Sphere<T> {}
+
+impl<T: Material> Solid for MovingSphere<T> {}
+
+impl<T: Material> Solid for Cuboid<T> {}
+
+impl<T: Material> Solid for CappedQuadric<T> {}
+
+impl<T: Material> Solid for Torus<T> {}
+
+impl<S: Sdf, T: Material> Solid for SdfShape<S, T> {}
+
+impl<T: Material> Solid for ClosedMesh<T> {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// The first solid without the second.
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        }
+    }
+}
+
+/// A boolean combination of two solids. Each part of its surface keeps the material of the solid
+/// it comes from, and the surfaces of the second solid bounding a difference face inwards of it.
+pub struct Csg<A: Solid, B: Solid> {
+    a: A,
+    b: B,
+    operation: Operation,
+}
+
+impl<A: Solid, B: Solid> Csg<A, B> {
+    pub fn new(operation: Operation, a: A, b: B) -> Self {
+        Csg { a, b, operation }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Self::new(Operation::Union, a, b)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Self::new(Operation::Intersection, a, b)
+    }
+
+    pub fn difference(a: A, b: B) -> Self {
+        Self::new(Operation::Difference, a, b)
+    }
+}
+
+/// A boundary of a span of one of the operands of a `Csg`.
+struct Event<'s, 'a> {
+    t: Num,
+    second: bool,
+    enter: bool,
+    rec: &'s Option<HitRecord<'a>>,
+}
+
+fn events<'s, 'a>(spans: &'s [Span<'a>], second: bool) -> impl Iterator<Item = Event<'s, 'a>> + 's {
+    spans.iter().flat_map(move |span| {
+        let enter = Event {
+            t: span.interval.min,
+            second,
+            enter: true,
+            rec: &span.enter,
+        };
+        let exit = Event {
+            t: span.interval.max,
+            second,
+            enter: false,
+            rec: &span.exit,
+        };
+        iter::once(enter).chain(iter::once(exit))
+    })
+}
+
+impl<A: Solid, B: Solid> Solid for Csg<A, B> {
+    fn spans<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, spans: &mut Vec<Span<'a>>) {
+        let mut a = Vec::new();
+        self.a.spans(r, t_min, t_max, &mut a);
+        if a.is_empty() && self.operation != Operation::Union {
+            return;
+        }
+        let mut b = Vec::new();
+        self.b.spans(r, t_min, t_max, &mut b);
+
+        // Sweeps the boundaries of both operands in order. Exits go first on ties so solids
+        // touching each other don't leave empty spans.
+        let mut events = events(&a, false)
+            .chain(events(&b, true))
+            .collect::<Vec<_>>();
+        events.sort_by(|x, y| {
+            x.t.partial_cmp(&y.t)
+                .unwrap()
+                .then_with(|| x.enter.cmp(&y.enter))
+        });
+
+        let start = spans.len();
+        let (mut inside_a, mut inside_b) = (false, false);
+        for event in events {
+            let was_inside = self.operation.inside(inside_a, inside_b);
+            if event.second {
+                inside_b = event.enter;
+            } else {
+                inside_a = event.enter;
+            }
+            let inside = self.operation.inside(inside_a, inside_b);
+            if inside == was_inside {
+                continue;
+            }
+
+            let mut rec = event.rec.clone();
+            if event.second && self.operation == Operation::Difference {
+                if let Some(rec) = &mut rec {
+                    rec.normal = -rec.normal;
+                    rec.geometric_normal = -rec.geometric_normal;
+                    rec.front_face = !rec.front_face;
+                }
+            }
+
+            if inside {
+                // Merges spans meeting where one operand takes over from the other.
+                match spans[start..].last_mut() {
+                    Some(last) if last.interval.max == event.t => last.exit = None,
+                    _ => spans.push(Span {
+                        interval: Interval::point(event.t),
+                        enter: rec,
+                        exit: None,
+                    }),
+                }
+            } else if let Some(last) = spans.last_mut() {
+                last.interval.max = event.t;
+                last.exit = rec;
+            }
+        }
+    }
+}
+
+impl<A: Solid, B: Solid> Hitable for Csg<A, B> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let mut spans = Vec::new();
+        self.spans(r, t_min, t_max, &mut spans);
+        let closest = spans
+            .into_iter()
+            .flat_map(|span| span.enter.into_iter().chain(span.exit))
+            .next();
+        match closest {
+            Some(closest) => {
+                *rec = closest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        let a = self.a.bounding_box(t0, t1);
+        let b = self.b.bounding_box(t0, t1);
+        match self.operation {
+            Operation::Union => Some(a?.surrounding_box(&b?)),
+            Operation::Intersection => match (a, b) {
+                (Some(a), Some(b)) => Some(BoundingBox::new(
+                    a.min().max(&b.min()),
+                    a.max().min(&b.max()),
+                )),
+                (a, b) => a.or(b),
+            },
+            Operation::Difference => a,
+        }
+    }
+}
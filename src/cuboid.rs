@@ -0,0 +1,187 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable, Sampleable};
+use crate::material::Material;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+/// An axis-aligned box. Each face has its own texture coordinates, going from 0 to 1 along the
+/// next two axes after its normal in cyclic order, mirrored on the faces towards the negative
+/// side so they all face outwards.
+pub struct Cuboid<T: Material> {
+    min: Vec3,
+    max: Vec3,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Cuboid<T> {}
+
+impl<T: Material> Cuboid<T> {
+    /// The box with opposite corners `p0` and `p1`.
+    pub fn new(p0: Vec3, p1: Vec3, material: T) -> Self {
+        let (min, max) = p0.min_max(&p1);
+        Cuboid { min, max, material }
+    }
+
+    /// The distances along `r` to the faces where it enters and leaves the box, with the axis of
+    /// each face.
+    fn slabs(&self, r: &Ray) -> ((Num, usize), (Num, usize)) {
+        let mut enter = (-MAX_NUM, 0);
+        let mut leave = (MAX_NUM, 0);
+        for axis in 0..3 {
+            let a = (self.min[axis] - r.origin()[axis]) / r.direction()[axis];
+            let b = (self.max[axis] - r.origin()[axis]) / r.direction()[axis];
+            let (near, far) = if a <= b { (a, b) } else { (b, a) };
+            if near > enter.0 {
+                enter = (near, axis);
+            }
+            if far < leave.0 {
+                leave = (far, axis);
+            }
+        }
+        (enter, leave)
+    }
+
+    /// The first face crossed by `r` between `t_min` and `t_max`. Rays starting on a face get
+    /// `t = 0` for it, so they can't hit it again.
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, usize, bool)> {
+        let ((t0, axis0), (t1, axis1)) = self.slabs(r);
+        if t0 > t1 {
+            return None;
+        }
+
+        if t0 > t_min && t0 < t_max {
+            // The ray enters through the face looking against it.
+            Some((t0, axis0, r.direction()[axis0] > 0.0))
+        } else if t1 > t_min && t1 < t_max {
+            Some((t1, axis1, r.direction()[axis1] < 0.0))
+        } else {
+            None
+        }
+    }
+
+    fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    fn face_area(&self, axis: usize) -> Num {
+        let size = self.size();
+        size[(axis + 1) % 3] * size[(axis + 2) % 3]
+    }
+}
+
+impl<T: Material> Hitable for Cuboid<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let (t, axis, negative) = match self.intersect(r, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        // The hit point lies exactly on the plane of the face, and errors along the plane don't
+        // matter to rays leaving it.
+        let mut p = r.point_at(t).max(&self.min).min(&self.max);
+        p[axis] = if negative {
+            self.min[axis]
+        } else {
+            self.max[axis]
+        };
+
+        let (a_axis, b_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let size = self.size();
+        let sign = if negative { -1.0 } else { 1.0 };
+        let mut normal = Vec3::default();
+        normal[axis] = sign;
+        let u = (p[a_axis] - self.min[a_axis]) / size[a_axis];
+
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = Vec3::default();
+        rec.set_normals(r, normal, normal);
+        rec.u = if negative { 1.0 - u } else { u };
+        rec.v = (p[b_axis] - self.min[b_axis]) / size[b_axis];
+        rec.dpdu = Vec3::default();
+        rec.dpdu[a_axis] = sign * size[a_axis];
+        rec.dpdv = Vec3::default();
+        rec.dpdv[b_axis] = size[b_axis];
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.min, self.max))
+    }
+}
+
+impl<T: Material> Sampleable for Cuboid<T> {
+    fn area(&self) -> Num {
+        2.0 * (0..3).map(|axis| self.face_area(axis)).sum::<Num>()
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        // Picks a face with a probability proportional to its area and reuses what's left of `u`
+        // to place the point on it.
+        let mut remaining = u * self.area();
+        let mut face = (2, false);
+        for axis in 0..3 {
+            let area = self.face_area(axis);
+            if remaining < area {
+                face = (axis, true);
+                break;
+            }
+            remaining -= area;
+            if remaining < area {
+                face = (axis, false);
+                break;
+            }
+            remaining -= area;
+        }
+
+        let (axis, negative) = face;
+        let (a_axis, b_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let size = self.size();
+        let u = max(0.0, min(remaining / self.face_area(axis), 1.0));
+
+        let mut p = self.min;
+        p[axis] = if negative {
+            self.min[axis]
+        } else {
+            self.max[axis]
+        };
+        p[a_axis] += u * size[a_axis];
+        p[b_axis] += v * size[b_axis];
+        let mut normal = Vec3::default();
+        normal[axis] = if negative { -1.0 } else { 1.0 };
+        (p, normal)
+    }
+
+    /// A direction can reach a sampled point on either of the two faces it crosses, so both add
+    /// to its density.
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Num {
+        let r = Ray::new(origin, direction);
+        let ((t0, axis0), (t1, axis1)) = self.slabs(&r);
+        if t0 > t1 || t1 <= 0.0 {
+            return 0.0;
+        }
+
+        let area = self.area();
+        let len = direction.len();
+        let face_pdf = |t: Num, axis: usize| {
+            let distance = t * len;
+            let cos = direction[axis].abs() / len;
+            distance * distance / (cos * area)
+        };
+
+        let pdf = face_pdf(t1, axis1);
+        if t0 > 0.0 {
+            pdf + face_pdf(t0, axis0)
+        } else {
+            pdf
+        }
+    }
+}
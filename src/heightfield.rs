@@ -0,0 +1,281 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable, TraversalStats};
+use crate::material::Material;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::triangle::{self, ShearedRay};
+use crate::vec3::Vec3;
+
+/// A terrain given by a grid of heights along the `y` axis, with each cell split in two
+/// triangles. It's traversed with a 2D DDA over the cells, skipping the ones the ray passes
+/// above or below. Its normals are interpolated from the slopes of the grid at each sample, and
+/// its texture coordinates go from 0 to 1 along `x` and `z` across the grid.
+pub struct Heightfield<T: Material> {
+    corner: Vec3,
+    cell_size: Num,
+    nx: usize,
+    nz: usize,
+    heights: Vec<Num>,
+    bounding_box: BoundingBox,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Heightfield<T> {}
+
+impl<T: Material> Heightfield<T> {
+    /// The terrain with `nx` by `nz` samples `cell_size` apart, starting at `corner` and going
+    /// towards `+x` and `+z`. The height of the sample `(i, j)` is `heights[j * nx + i]`, on top
+    /// of the height of `corner`.
+    pub fn new(
+        corner: Vec3,
+        cell_size: Num,
+        nx: usize,
+        nz: usize,
+        heights: Vec<Num>,
+        material: T,
+    ) -> Self {
+        assert!(nx > 1 && nz > 1);
+        assert_eq!(heights.len(), nx * nz);
+
+        let lo = heights.iter().cloned().fold(MAX_NUM, min);
+        let hi = heights.iter().cloned().fold(-MAX_NUM, max);
+        let extent = Vec3::new((nx - 1) as Num, 0.0, (nz - 1) as Num) * cell_size;
+        let bounding_box = BoundingBox::new(
+            corner + Vec3::new(0.0, lo, 0.0),
+            corner + extent + Vec3::new(0.0, hi, 0.0),
+        );
+
+        Heightfield {
+            corner,
+            cell_size,
+            nx,
+            nz,
+            heights,
+            bounding_box,
+            material,
+        }
+    }
+
+    fn height(&self, i: usize, j: usize) -> Num {
+        self.heights[j * self.nx + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        self.corner
+            + Vec3::new(
+                i as Num * self.cell_size,
+                self.height(i, j),
+                j as Num * self.cell_size,
+            )
+    }
+
+    /// The normal at the sample `(i, j)` from the central differences of the heights around it,
+    /// or one sided ones on the border.
+    fn slope_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (x0, x1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (z0, z1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+        let dx = (self.height(x1, j) - self.height(x0, j)) / ((x1 - x0) as Num * self.cell_size);
+        let dz = (self.height(i, z1) - self.height(i, z0)) / ((z1 - z0) as Num * self.cell_size);
+        Vec3::new(-dx, 1.0, -dz).unit()
+    }
+
+    /// The samples at the corners of triangle `k` of the grid, wound so they face `+y`.
+    fn corners(&self, k: usize) -> [(usize, usize); 3] {
+        let cell = k / 2;
+        let (i, j) = (cell % (self.nx - 1), cell / (self.nx - 1));
+        if k & 1 == 0 {
+            [(i, j), (i, j + 1), (i + 1, j + 1)]
+        } else {
+            [(i, j), (i + 1, j + 1), (i + 1, j)]
+        }
+    }
+
+    fn intersect(
+        &self,
+        k: usize,
+        r: &ShearedRay,
+        t_min: Num,
+        t_max: Num,
+    ) -> Option<(Num, Num, Num)> {
+        let [a, b, c] = self.corners(k);
+        r.intersect(
+            self.vertex(a.0, a.1),
+            self.vertex(b.0, b.1),
+            self.vertex(c.0, c.1),
+            t_min,
+            t_max,
+        )
+    }
+
+    /// Walks the cells crossed by `r` in order, calling `f` with the triangles of the ones where
+    /// the ray could hit the terrain, until it returns `true`.
+    fn traverse(
+        &self,
+        r: &Ray,
+        t_min: Num,
+        closest: &mut Num,
+        stats: &mut TraversalStats,
+        mut f: impl FnMut(usize, &mut Num, &mut TraversalStats) -> bool,
+    ) {
+        // Rounding errors could make rays miss a flat terrain, so the box is padded along `y`.
+        let (lo, hi) = (self.bounding_box.min(), self.bounding_box.max());
+        let pad = Vec3::new(0.0, gamma(4) * max(lo.y().abs(), hi.y().abs()), 0.0);
+        let (t0, t1) = match BoundingBox::new(lo - pad, hi + pad).intersect(r, t_min, *closest) {
+            Some(range) => range,
+            None => return,
+        };
+
+        let origin = r.origin();
+        let direction = r.direction();
+        let start = r.point_at(t0) - self.corner;
+        let resolution = [self.nx - 1, self.nz - 1];
+
+        let mut cell = [0isize; 2];
+        let mut step = [0isize; 2];
+        let mut out = [0isize; 2];
+        let mut next = [MAX_NUM; 2];
+        let mut delta = [MAX_NUM; 2];
+        for (axis, &world) in [0, 2].iter().enumerate() {
+            let i = (start.get(world) / self.cell_size).floor();
+            let i = max(0.0, min(i, (resolution[axis] - 1) as Num)) as usize;
+            cell[axis] = i as isize;
+            let d = direction.get(world);
+            let lo = self.corner.get(world);
+            if d > 0.0 {
+                let boundary = lo + (i + 1) as Num * self.cell_size;
+                next[axis] = (boundary - origin.get(world)) / d;
+                delta[axis] = self.cell_size / d;
+                step[axis] = 1;
+                out[axis] = resolution[axis] as isize;
+            } else if d < 0.0 {
+                let boundary = lo + i as Num * self.cell_size;
+                next[axis] = (boundary - origin.get(world)) / d;
+                delta[axis] = -self.cell_size / d;
+                step[axis] = -1;
+                out[axis] = -1;
+            }
+        }
+
+        let mut t_enter = t0;
+        loop {
+            let axis = if next[0] < next[1] { 0 } else { 1 };
+            let t_exit = min(next[axis], t1);
+
+            stats.node_tests += 1;
+            let (i, j) = (cell[0] as usize, cell[1] as usize);
+            let samples = [
+                self.height(i, j),
+                self.height(i + 1, j),
+                self.height(i, j + 1),
+                self.height(i + 1, j + 1),
+            ];
+            let cell_lo = samples.iter().cloned().fold(MAX_NUM, min) + self.corner.y();
+            let cell_hi = samples.iter().cloned().fold(-MAX_NUM, max) + self.corner.y();
+            let (y0, y1) = (r.point_at(t_enter).y(), r.point_at(t_exit).y());
+            let margin = gamma(4) * (origin.y().abs() + max(y0.abs(), y1.abs()) + cell_hi.abs());
+            if min(y0, y1) <= cell_hi + margin && max(y0, y1) >= cell_lo - margin {
+                let k = 2 * (j * resolution[0] + i);
+                if f(k, closest, stats) || f(k + 1, closest, stats) {
+                    return;
+                }
+            }
+
+            if *closest <= t_exit || next[axis] >= t1 {
+                return;
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] == out[axis] {
+                return;
+            }
+            t_enter = next[axis];
+            next[axis] += delta[axis];
+        }
+    }
+}
+
+impl<T: Material> Hitable for Heightfield<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.hit_counting(r, t_min, t_max, rec, &mut TraversalStats::default())
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        let sheared = ShearedRay::new(r);
+        let mut occluded = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        self.traverse(r, t_min, &mut closest, &mut stats, |k, closest, _| {
+            occluded = self.intersect(k, &sheared, t_min, *closest).is_some();
+            occluded
+        });
+        occluded
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let sheared = ShearedRay::new(r);
+        let mut hit = None;
+        let mut closest = t_max;
+        self.traverse(r, t_min, &mut closest, stats, |k, closest, stats| {
+            stats.primitive_tests += 1;
+            if let Some((temp, u, v)) = self.intersect(k, &sheared, t_min, *closest) {
+                hit = Some((k, u, v));
+                *closest = temp;
+            }
+            false
+        });
+
+        let (k, u, v) = match hit {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let corners = self.corners(k);
+        let p = [
+            self.vertex(corners[0].0, corners[0].1),
+            self.vertex(corners[1].0, corners[1].1),
+            self.vertex(corners[2].0, corners[2].1),
+        ];
+        let normals = [
+            self.slope_normal(corners[0].0, corners[0].1),
+            self.slope_normal(corners[1].0, corners[1].1),
+            self.slope_normal(corners[2].0, corners[2].1),
+        ];
+        let uv = |(i, j): (usize, usize)| {
+            [
+                i as Num / (self.nx - 1) as Num,
+                j as Num / (self.nz - 1) as Num,
+            ]
+        };
+        let uvs = [uv(corners[0]), uv(corners[1]), uv(corners[2])];
+
+        let normal = triangle::interpolate(&normals, u, v);
+        let (point, p_error) = triangle::barycentric_point(p, u, v);
+        rec.t = closest;
+        rec.p = point;
+        rec.p_error = p_error;
+        rec.set_normals(
+            r,
+            triangle::geometric_normal(p[0], p[1], p[2], normal),
+            normal,
+        );
+        let (u, v, dpdu, dpdv) = triangle::texture_coordinates(Some(&uvs), p, u, v);
+        rec.u = u;
+        rec.v = v;
+        rec.dpdu = dpdu;
+        rec.dpdv = dpdv;
+        rec.primitive_id = k;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(self.bounding_box.clone())
+    }
+}
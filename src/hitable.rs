@@ -1,13 +1,35 @@
 use crate::bounding_box::BoundingBox;
 use crate::material::{Dummy, Material};
 use crate::num::*;
+use crate::packet::{RayPacket, PACKET_SIZE};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
+use rand::prelude::{Rng, ThreadRng};
+
+#[derive(Clone)]
 pub struct HitRecord<'a> {
     pub t: Num,
     pub p: Vec3,
+    /// A bound on the absolute rounding error of each coordinate of `p`.
+    pub p_error: Vec3,
+    /// The shading normal, pointing outwards of the surface.
     pub normal: Vec3,
+    /// The true normal of the surface, pointing to the same side as `normal`.
+    pub geometric_normal: Vec3,
+    /// Whether the ray hit the outer side of the surface.
+    pub front_face: bool,
+    /// The texture coordinates of the hit point. For triangles without texture coordinates
+    /// these are the barycentric coordinates with respect to the second and third vertices.
+    pub u: Num,
+    pub v: Num,
+    /// The partial derivatives of the hit point with respect to `u` and `v`.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// The index of the hit primitive inside its mesh, zero for standalone primitives.
+    pub primitive_id: usize,
+    /// The id of the `Instance` the hit primitive belongs to, if any.
+    pub instance_id: Option<usize>,
     pub material: &'a dyn Material,
 }
 
@@ -16,10 +38,49 @@ impl<'a> HitRecord<'a> {
         HitRecord {
             t,
             p,
+            p_error: Vec3::default(),
             normal,
+            geometric_normal: normal,
+            front_face: true,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            primitive_id: 0,
+            instance_id: None,
             material,
         }
     }
+
+    /// Sets the geometric and shading normals, both facing outwards, and whether `r` hit the
+    /// outer side of the surface.
+    pub fn set_normals(&mut self, r: &Ray, geometric_normal: Vec3, normal: Vec3) {
+        self.geometric_normal = geometric_normal;
+        self.normal = normal;
+        self.front_face = r.direction().dot(geometric_normal) < 0.0;
+    }
+
+    /// A ray leaving the hit point towards `direction` at `time`. Its origin is moved along the
+    /// geometric normal, to the side `direction` points to, just past the error bounds of `p` so
+    /// the ray can't hit the same surface again whatever the scale of the scene.
+    pub fn spawn_ray(&self, direction: Vec3, time: Num) -> Ray {
+        let n = self.geometric_normal;
+        let mut offset = n.abs().dot(self.p_error) * n;
+        if direction.dot(n) < 0.0 {
+            offset = -offset;
+        }
+
+        // Round away from `p` so the offset isn't lost when adding it.
+        let mut origin = self.p + offset;
+        for axis in 0..3 {
+            if offset[axis] > 0.0 {
+                origin[axis] = origin[axis].next_up();
+            } else if offset[axis] < 0.0 {
+                origin[axis] = origin[axis].next_down();
+            }
+        }
+        Ray::with_time(origin, direction, time)
+    }
 }
 
 impl<'a> Default for HitRecord<'a> {
@@ -33,12 +94,92 @@ impl<'a> Default for HitRecord<'a> {
     }
 }
 
+/// Counters of the work done to intersect a ray with the world.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraversalStats {
+    pub node_tests: usize,
+    pub primitive_tests: usize,
+}
+
 pub trait Hitable: Sync {
     fn hit<'a>(&'a self, _: &Ray, _: Num, _: Num, _: &mut HitRecord<'a>) -> bool {
         false
     }
 
+    /// Whether anything is hit between `t_min` and `t_max`, without looking for the closest hit.
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.hit(r, t_min, t_max, &mut HitRecord::default())
+    }
+
+    /// Intersects the rays of `packet` whose lane is set in `active`. `t_max` holds the closest
+    /// hit found so far for each lane and is updated alongside `recs`. Returns the lanes that hit
+    /// something closer than their previous `t_max`.
+    fn hit_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        t_min: Num,
+        t_max: &mut Numx4,
+        active: Maskx4,
+        recs: &mut [HitRecord<'a>; PACKET_SIZE],
+    ) -> Maskx4 {
+        let mut hit = Maskx4::splat(false);
+        for (lane, rec) in recs.iter_mut().enumerate() {
+            if active.extract(lane) && self.hit(packet.ray(lane), t_min, t_max.extract(lane), rec) {
+                hit = hit.replace(lane, true);
+                *t_max = t_max.replace(lane, rec.t);
+            }
+        }
+        hit
+    }
+
+    /// Same as `hit` but also records the number of nodes and primitives tested in `stats`.
+    /// Collections override it so the tests done by their elements are counted too.
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        stats.primitive_tests += 1;
+        self.hit(r, t_min, t_max, rec)
+    }
+
     fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
         None
     }
 }
+
+/// A surface with a finite area that can be sampled, so it can be used as an area light.
+pub trait Sampleable: Hitable {
+    fn area(&self) -> Num;
+
+    /// Maps `u` and `v`, uniformly distributed in [0, 1), to a point uniformly distributed over
+    /// the surface. Returns the point and the outward normal there.
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3);
+
+    /// A direction from `origin` towards a random point of the surface.
+    fn sample_direction(&self, origin: Vec3, rng: &mut ThreadRng) -> Vec3 {
+        self.sample(rng.gen(), rng.gen()).0 - origin
+    }
+
+    /// The density of `sample_direction` returning `direction` from `origin`, with respect to
+    /// solid angle. It's zero if `direction` misses the surface.
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Num {
+        let mut rec = HitRecord::default();
+        if self.hit(&Ray::new(origin, direction), 0.0, MAX_NUM, &mut rec) {
+            area_pdf(&rec, direction, self.area())
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Turns the density `1 / area` of sampling the point of `rec` by area into a density with respect
+/// to the solid angle seen from the origin of the ray along `direction` that hit it.
+pub(crate) fn area_pdf(rec: &HitRecord, direction: Vec3, area: Num) -> Num {
+    let distance = rec.t * direction.len();
+    let cos = direction.dot(rec.geometric_normal).abs() / direction.len();
+    distance * distance / (cos * area)
+}
@@ -0,0 +1,142 @@
+use crate::num::*;
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// A closed range of numbers. Intervals with `min > max` are empty.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Interval {
+    pub min: Num,
+    pub max: Num,
+}
+
+impl Interval {
+    pub const EMPTY: Interval = Interval {
+        min: MAX_NUM,
+        max: -MAX_NUM,
+    };
+
+    pub const UNIVERSE: Interval = Interval {
+        min: -MAX_NUM,
+        max: MAX_NUM,
+    };
+
+    pub fn new(min: Num, max: Num) -> Self {
+        Interval { min, max }
+    }
+
+    /// The interval containing only `x`.
+    pub fn point(x: Num) -> Self {
+        Self::new(x, x)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    /// The length of the interval, zero when empty.
+    pub fn len(&self) -> Num {
+        max(self.max - self.min, 0.0)
+    }
+
+    pub fn midpoint(&self) -> Num {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn contains(&self, x: Num) -> bool {
+        self.min <= x && x <= self.max
+    }
+
+    /// Whether `x` lies strictly inside the interval.
+    pub fn surrounds(&self, x: Num) -> bool {
+        self.min < x && x < self.max
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        !self.intersect(other).is_empty()
+    }
+
+    pub fn clamp(&self, x: Num) -> Num {
+        max(self.min, min(x, self.max))
+    }
+
+    /// The interval grown by `delta` on each side.
+    pub fn expand(&self, delta: Num) -> Self {
+        Self::new(self.min - delta, self.max + delta)
+    }
+
+    /// The smallest interval containing both intervals.
+    pub fn union(&self, other: &Interval) -> Self {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        Self::new(min(self.min, other.min), max(self.max, other.max))
+    }
+
+    pub fn intersect(&self, other: &Interval) -> Self {
+        Self::new(max(self.min, other.min), min(self.max, other.max))
+    }
+}
+
+impl Default for Interval {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Interval arithmetic, the result contains every possible result of the operation on numbers
+/// of the operands.
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, other: Interval) -> Interval {
+        Interval::new(self.min + other.min, self.max + other.max)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, other: Interval) -> Interval {
+        Interval::new(self.min - other.max, self.max - other.min)
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        Interval::new(-self.max, -self.min)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, other: Interval) -> Interval {
+        let products = [
+            self.min * other.min,
+            self.min * other.max,
+            self.max * other.min,
+            self.max * other.max,
+        ];
+        Interval::new(
+            products.iter().cloned().fold(MAX_NUM, min),
+            products.iter().cloned().fold(-MAX_NUM, max),
+        )
+    }
+}
+
+impl Mul<Num> for Interval {
+    type Output = Interval;
+
+    fn mul(self, s: Num) -> Interval {
+        if s >= 0.0 {
+            Interval::new(self.min * s, self.max * s)
+        } else {
+            Interval::new(self.max * s, self.min * s)
+        }
+    }
+}
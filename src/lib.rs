@@ -1,18 +1,36 @@
 pub mod bounding_box;
 pub mod camera;
 pub mod collections;
+pub mod csg;
+pub mod cuboid;
+pub mod heightfield;
 pub mod hitable;
+pub mod interval;
 pub mod material;
+pub mod matrix;
 pub mod num;
+pub mod packet;
+pub mod plane;
+pub mod poly;
+pub mod quadric;
+pub mod quaternion;
 pub mod ray;
+pub mod rect;
+pub mod sdf;
 pub mod sphere;
 pub mod stl;
+pub mod texture;
+pub mod torus;
+pub mod transform;
+pub mod transformed;
 pub mod triangle;
+pub mod triangle_mesh;
 pub mod vec3;
 
 use crate::camera::Camera;
-use crate::hitable::{HitRecord, Hitable};
-use crate::num::{Int, Num, MAX_NUM};
+use crate::hitable::{HitRecord, Hitable, TraversalStats};
+use crate::num::{Int, Maskx4, Num, Numx4, MAX_NUM};
+use crate::packet::{RayPacket, PACKET_SIZE};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
@@ -22,13 +40,46 @@ use std::io::{BufWriter, Write};
 use rand::prelude::*;
 use rayon::prelude::*;
 
-fn color(mut r: Ray, world: &dyn Hitable, rng: &mut ThreadRng) -> Vec3 {
+fn color(r: Ray, world: &dyn Hitable, rng: &mut ThreadRng) -> Vec3 {
     let mut rec = HitRecord::default();
-    let mut color = Vec3::from_scalar(1.0);
+    let hit = world.hit(&r, 0.0, MAX_NUM, &mut rec);
+    shade(r, hit, rec, world, rng)
+}
+
+/// Traces the rays of `packet` together up to their first hit, then follows each path on its own.
+fn color_packet(packet: RayPacket, world: &dyn Hitable, rng: &mut ThreadRng) -> Vec3 {
+    let mut recs = [
+        HitRecord::default(),
+        HitRecord::default(),
+        HitRecord::default(),
+        HitRecord::default(),
+    ];
+    let mut t_max = Numx4::splat(MAX_NUM);
+    let hit = world.hit_packet(&packet, 0.0, &mut t_max, Maskx4::splat(true), &mut recs);
+
+    let rays = packet.into_rays();
+    let mut color = Vec3::default();
+    for (lane, (r, rec)) in rays.iter().zip(recs.iter()).enumerate() {
+        color += shade(r.clone(), hit.extract(lane), rec.clone(), world, rng);
+    }
+    color
+}
+
+/// Follows the path of `r` given whether it hit something and the record of that hit.
+fn shade<'a>(
+    mut r: Ray,
+    mut hit: bool,
+    mut rec: HitRecord<'a>,
+    world: &'a dyn Hitable,
+    rng: &mut ThreadRng,
+) -> Vec3 {
+    let mut throughput = Vec3::from_scalar(1.0);
+    let mut color = Vec3::default();
     let mut depth = 0;
 
     loop {
-        if world.hit(&r, 0.001, MAX_NUM, &mut rec) {
+        if hit {
+            color += throughput * rec.material.emitted(&rec);
             let mut scattered = Ray::default();
             let mut attenuation = Vec3::default();
             if depth < 50
@@ -36,16 +87,16 @@ fn color(mut r: Ray, world: &dyn Hitable, rng: &mut ThreadRng) -> Vec3 {
                     .material
                     .scatter(&r, &rec, &mut attenuation, &mut scattered, rng)
             {
-                color *= attenuation;
+                throughput *= attenuation;
                 r = scattered;
                 depth += 1;
                 rec = HitRecord::default();
+                hit = world.hit(&r, 0.0, MAX_NUM, &mut rec);
             } else {
-                color = Vec3::default();
                 break;
             }
         } else {
-            color *= background_color(&r);
+            color += throughput * background_color(&r);
             break;
         }
     }
@@ -65,10 +116,6 @@ pub fn render<T: Hitable>(
     ny: Int,
     ns: Int,
 ) -> std::io::Result<()> {
-    let mut file = BufWriter::new(File::create(path)?);
-
-    write!(&mut file, "P3\n{} {}\n255\n", nx, ny)?;
-
     let num_nx = nx as Num;
     let num_ny = ny as Num;
 
@@ -82,22 +129,102 @@ pub fn render<T: Hitable>(
                 .map(|i| {
                     let i = i as Num;
                     let mut col = Vec3::default();
-                    for _ in 0..ns {
+                    let sample = |rng: &mut ThreadRng| {
                         let u = (i + rng.gen::<Num>()) / num_nx;
                         let v = (j + rng.gen::<Num>()) / num_ny;
 
-                        let r = camera.get_ray(u, v, &mut rng);
+                        camera.get_ray(u, v, rng)
+                    };
 
+                    // Samples of the same pixel are coherent so they are traced as packets.
+                    let packets = ns as usize / PACKET_SIZE;
+                    for _ in 0..packets {
+                        let rays = [
+                            sample(&mut rng),
+                            sample(&mut rng),
+                            sample(&mut rng),
+                            sample(&mut rng),
+                        ];
+                        col += color_packet(RayPacket::new(rays), &world, &mut rng);
+                    }
+                    for _ in packets * PACKET_SIZE..ns as usize {
+                        let r = sample(&mut rng);
                         col += color(r, &world, &mut rng)
                     }
                     col /= ns as Num;
 
-                    255.99 * col.sqrt()
+                    // Lights can make a pixel brighter than white.
+                    255.99 * col.sqrt().min(&Vec3::from_scalar(1.0))
                 })
                 .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>();
 
+    write_ppm(path, nx, ny, colors)
+}
+
+/// Renders an image where the colour of each pixel shows the number of nodes and primitives
+/// tested by its primary ray, going from blue for the cheapest ray to red for the most expensive
+/// one.
+pub fn render_heatmap<T: Hitable>(
+    path: &str,
+    world: T,
+    camera: Camera,
+    nx: Int,
+    ny: Int,
+) -> std::io::Result<()> {
+    let num_nx = nx as Num;
+    let num_ny = ny as Num;
+
+    let counts = (0..ny as usize)
+        .into_par_iter()
+        .rev()
+        .flat_map(|j| {
+            let mut rng = rand::thread_rng();
+            let j = j as Num;
+            (0..nx)
+                .map(|i| {
+                    let i = i as Num;
+                    let u = (i + 0.5) / num_nx;
+                    let v = (j + 0.5) / num_ny;
+
+                    let r = camera.get_ray(u, v, &mut rng);
+
+                    let mut stats = TraversalStats::default();
+                    world.hit_counting(&r, 0.0, MAX_NUM, &mut HitRecord::default(), &mut stats);
+                    stats.node_tests + stats.primitive_tests
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let min_count = counts.iter().cloned().min().unwrap_or(0);
+    let max_count = counts.iter().cloned().max().unwrap_or(0);
+    let range = (max_count - min_count).max(1) as Num;
+
+    let colors = counts.into_iter().map(|count| {
+        let t = (count - min_count) as Num / range;
+        let col = if t < 0.5 {
+            Vec3::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+        } else {
+            Vec3::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+        };
+        255.99 * col
+    });
+
+    write_ppm(path, nx, ny, colors)
+}
+
+fn write_ppm(
+    path: &str,
+    nx: Int,
+    ny: Int,
+    colors: impl IntoIterator<Item = Vec3>,
+) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    write!(&mut file, "P3\n{} {}\n255\n", nx, ny)?;
+
     for i in colors {
         writeln!(
             &mut file,
@@ -1,6 +1,7 @@
 use crate::hitable::HitRecord;
 use crate::num::*;
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
 
 use rand::prelude::{Rng, ThreadRng};
@@ -24,53 +25,71 @@ pub trait Material {
     ) -> bool {
         false
     }
+
+    /// The light emitted at the hit point.
+    fn emitted(&self, _: &HitRecord) -> Vec3 {
+        Vec3::default()
+    }
 }
 
 #[derive(Clone)]
-pub struct Lambertian {
-    albedo: Vec3,
+pub struct Lambertian<T: Texture = SolidColor> {
+    albedo: T,
 }
 
 impl Lambertian {
     pub fn new(albedo_x: Num, albedo_y: Num, albedo_z: Num) -> Self {
         Lambertian {
-            albedo: Vec3::new(albedo_x, albedo_y, albedo_z),
+            albedo: SolidColor::new(albedo_x, albedo_y, albedo_z),
         }
     }
 }
 
-impl Material for Lambertian {
+impl<T: Texture> Lambertian<T> {
+    pub fn with_texture(albedo: T) -> Self {
+        Lambertian { albedo }
+    }
+}
+
+impl<T: Texture> Material for Lambertian<T> {
     fn scatter(
         &self,
-        _: &Ray,
+        r_in: &Ray,
         rec: &HitRecord,
         attenuation: &mut Vec3,
         scattered: &mut Ray,
         rng: &mut ThreadRng,
     ) -> bool {
         let target = rec.p + rec.normal + random_in_unit_sphere(rng);
-        *scattered = Ray::new(rec.p, target - rec.p);
-        *attenuation = self.albedo;
+        *scattered = rec.spawn_ray(target - rec.p, r_in.time());
+        *attenuation = self.albedo.value(rec.u, rec.v, rec.p);
         true
     }
 }
 
 #[derive(Clone)]
-pub struct Metal {
-    albedo: Vec3,
-    fuzz: Num,
+pub struct Metal<A: Texture = SolidColor, F: Texture = Num> {
+    albedo: A,
+    fuzz: F,
 }
 
 impl Metal {
     pub fn new(albedo_x: Num, albedo_y: Num, albedo_z: Num, f: Num) -> Self {
         Metal {
-            albedo: Vec3::new(albedo_x, albedo_y, albedo_z),
+            albedo: SolidColor::new(albedo_x, albedo_y, albedo_z),
             fuzz: if f > 1.0 { 1.0 } else { f },
         }
     }
 }
 
-impl Material for Metal {
+impl<A: Texture, F: Texture> Metal<A, F> {
+    /// A metal whose albedo and roughness vary over its surface. The fuzz is clamped to 1.
+    pub fn with_textures(albedo: A, fuzz: F) -> Self {
+        Metal { albedo, fuzz }
+    }
+}
+
+impl<A: Texture, F: Texture> Material for Metal<A, F> {
     fn scatter(
         &self,
         r_in: &Ray,
@@ -79,9 +98,10 @@ impl Material for Metal {
         scattered: &mut Ray,
         rng: &mut ThreadRng,
     ) -> bool {
-        let reflected = reflect(r_in.direction().unit(), rec.normal);
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * random_in_unit_sphere(rng));
-        *attenuation = self.albedo;
+        let reflected = r_in.direction().unit().reflect(rec.normal);
+        let fuzz = min(self.fuzz.value(rec.u, rec.v, rec.p).x(), 1.0);
+        *scattered = rec.spawn_ray(reflected + fuzz * random_in_unit_sphere(rng), r_in.time());
+        *attenuation = self.albedo.value(rec.u, rec.v, rec.p);
         scattered.direction().dot(rec.normal) > 0.0
     }
 }
@@ -112,13 +132,12 @@ impl Material for Dielectric {
         rng: &mut ThreadRng,
     ) -> bool {
         let outward_normal;
-        let reflected = reflect(r_in.direction(), rec.normal);
+        let reflected = r_in.direction().reflect(rec.normal);
         let ratio;
         *attenuation = Vec3::from_scalar(1.0);
-        let mut refracted = Vec3::default();
         let mut cos = r_in.direction().dot(rec.normal) / r_in.direction().len();
         if r_in.direction().dot(rec.normal) > 0.0 {
-            outward_normal = -1.0 * rec.normal;
+            outward_normal = -rec.normal;
             ratio = self.ref_idx;
             cos *= self.ref_idx;
         } else {
@@ -126,34 +145,43 @@ impl Material for Dielectric {
             ratio = 1.0 / self.ref_idx;
             cos *= -1.0;
         }
-        if refract(r_in.direction(), outward_normal, ratio, &mut refracted)
-            && rng.gen::<Num>() >= self.schlick(cos)
-        {
-            *scattered = Ray::new(rec.p, refracted);
-        } else {
-            *scattered = Ray::new(rec.p, reflected);
+        match r_in.direction().unit().refract(outward_normal, ratio) {
+            Some(refracted) if rng.gen::<Num>() >= self.schlick(cos) => {
+                *scattered = rec.spawn_ray(refracted, r_in.time());
+            }
+            _ => *scattered = rec.spawn_ray(reflected, r_in.time()),
         }
         true
     }
 }
 
+/// A material emitting light from both sides of its surface without scattering any.
 #[derive(Clone)]
-pub struct Dummy;
+pub struct DiffuseLight<T: Texture = SolidColor> {
+    emit: T,
+}
 
-impl Material for Dummy {}
+impl DiffuseLight {
+    pub fn new(r: Num, g: Num, b: Num) -> Self {
+        DiffuseLight {
+            emit: SolidColor::new(r, g, b),
+        }
+    }
+}
 
-fn reflect(v: Vec3, n: Vec3) -> Vec3 {
-    v - 2.0 * v.dot(n) * n
+impl<T: Texture> DiffuseLight<T> {
+    pub fn with_texture(emit: T) -> Self {
+        DiffuseLight { emit }
+    }
 }
 
-fn refract(v: Vec3, n: Vec3, ratio: Num, refracted: &mut Vec3) -> bool {
-    let uv = v.unit();
-    let dt = uv.dot(n);
-    let discriminant = 1.0 - ratio * ratio * (1.0 - dt * dt);
-    if discriminant > 0.0 {
-        *refracted = ratio * (uv - n * dt) - n * discriminant.sqrt();
-        true
-    } else {
-        false
+impl<T: Texture> Material for DiffuseLight<T> {
+    fn emitted(&self, rec: &HitRecord) -> Vec3 {
+        self.emit.value(rec.u, rec.v, rec.p)
     }
 }
+
+#[derive(Clone)]
+pub struct Dummy;
+
+impl Material for Dummy {}
@@ -0,0 +1,313 @@
+use crate::num::*;
+use crate::vec3::Vec3;
+
+use std::ops::{Add, Index, Mul, Sub};
+
+/// A 3x3 matrix stored by rows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat3 {
+    rows: [Vec3; 3],
+}
+
+impl Mat3 {
+    pub fn new(rows: [[Num; 3]; 3]) -> Self {
+        Self::from_rows(
+            Vec3::new(rows[0][0], rows[0][1], rows[0][2]),
+            Vec3::new(rows[1][0], rows[1][1], rows[1][2]),
+            Vec3::new(rows[2][0], rows[2][1], rows[2][2]),
+        )
+    }
+
+    pub fn from_rows(r0: Vec3, r1: Vec3, r2: Vec3) -> Self {
+        Mat3 { rows: [r0, r1, r2] }
+    }
+
+    pub fn from_cols(c0: Vec3, c1: Vec3, c2: Vec3) -> Self {
+        Self::from_rows(c0, c1, c2).transpose()
+    }
+
+    pub fn identity() -> Self {
+        Self::diagonal(Vec3::from_scalar(1.0))
+    }
+
+    pub fn diagonal(d: Vec3) -> Self {
+        Self::from_rows(
+            Vec3::new(d.x(), 0.0, 0.0),
+            Vec3::new(0.0, d.y(), 0.0),
+            Vec3::new(0.0, 0.0, d.z()),
+        )
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        self.rows[i]
+    }
+
+    pub fn col(&self, j: usize) -> Vec3 {
+        Vec3::new(self.rows[0][j], self.rows[1][j], self.rows[2][j])
+    }
+
+    pub fn transpose(&self) -> Self {
+        Mat3 {
+            rows: [self.col(0), self.col(1), self.col(2)],
+        }
+    }
+
+    pub fn determinant(&self) -> Num {
+        self.rows[0].dot(self.rows[1].cross(self.rows[2]))
+    }
+
+    /// The inverse of the matrix, or `None` if it is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let [r0, r1, r2] = self.rows;
+        Some(Self::from_cols(r1.cross(r2), r2.cross(r0), r0.cross(r1)) * (1.0 / det))
+    }
+
+    /// The matrix of the absolute values of the elements.
+    pub fn abs(&self) -> Self {
+        Self::from_rows(self.rows[0].abs(), self.rows[1].abs(), self.rows[2].abs())
+    }
+
+    /// The largest absolute value of the elements.
+    pub fn max_norm(&self) -> Num {
+        let a = self.rows[0]
+            .abs()
+            .max(&self.rows[1].abs().max(&self.rows[2].abs()));
+        max(a.x(), max(a.y(), a.z()))
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Index<usize> for Mat3 {
+    type Output = Vec3;
+
+    fn index(&self, i: usize) -> &Vec3 {
+        &self.rows[i]
+    }
+}
+
+impl Add for Mat3 {
+    type Output = Mat3;
+
+    fn add(self, other: Mat3) -> Mat3 {
+        Mat3 {
+            rows: [
+                self.rows[0] + other.rows[0],
+                self.rows[1] + other.rows[1],
+                self.rows[2] + other.rows[2],
+            ],
+        }
+    }
+}
+
+impl Sub for Mat3 {
+    type Output = Mat3;
+
+    fn sub(self, other: Mat3) -> Mat3 {
+        Mat3 {
+            rows: [
+                self.rows[0] - other.rows[0],
+                self.rows[1] - other.rows[1],
+                self.rows[2] - other.rows[2],
+            ],
+        }
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: Mat3) -> Mat3 {
+        let row = |r: Vec3| r.x() * other.rows[0] + r.y() * other.rows[1] + r.z() * other.rows[2];
+        Mat3 {
+            rows: [row(self.rows[0]), row(self.rows[1]), row(self.rows[2])],
+        }
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.rows[0].dot(v),
+            self.rows[1].dot(v),
+            self.rows[2].dot(v),
+        )
+    }
+}
+
+impl Mul<Num> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, s: Num) -> Mat3 {
+        Mat3 {
+            rows: [self.rows[0] * s, self.rows[1] * s, self.rows[2] * s],
+        }
+    }
+}
+
+/// A 4x4 matrix stored by rows, one SIMD vector per row.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4 {
+    rows: [Numx4; 4],
+}
+
+impl Mat4 {
+    pub fn new(rows: [[Num; 4]; 4]) -> Self {
+        let row = |r: [Num; 4]| Numx4::new(r[0], r[1], r[2], r[3]);
+        Mat4 {
+            rows: [row(rows[0]), row(rows[1]), row(rows[2]), row(rows[3])],
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// The affine matrix applying `linear` and then moving by `translation`.
+    pub fn from_linear(linear: &Mat3, translation: Vec3) -> Self {
+        let row = |i: usize| {
+            let r = linear.row(i);
+            [r.x(), r.y(), r.z(), translation[i]]
+        };
+        Self::new([row(0), row(1), row(2), [0.0, 0.0, 0.0, 1.0]])
+    }
+
+    pub fn to_array(&self) -> [[Num; 4]; 4] {
+        let mut m = [[0.0; 4]; 4];
+        for (row, simd) in m.iter_mut().zip(self.rows.iter()) {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = simd.extract(j);
+            }
+        }
+        m
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Num {
+        self.rows[i].extract(j)
+    }
+
+    /// The upper left 3x3 block, the linear part of an affine transform.
+    pub fn linear(&self) -> Mat3 {
+        let row = |i: usize| Vec3::new(self.get(i, 0), self.get(i, 1), self.get(i, 2));
+        Mat3::from_rows(row(0), row(1), row(2))
+    }
+
+    /// The last column without its last element, the translation of an affine transform.
+    pub fn translation(&self) -> Vec3 {
+        Vec3::new(self.get(0, 3), self.get(1, 3), self.get(2, 3))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = self.to_array();
+        let mut t = [[0.0; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = m[j][i];
+            }
+        }
+        Self::new(t)
+    }
+
+    /// The inverse of the matrix computed by Gauss-Jordan elimination with partial pivoting, or
+    /// `None` if it is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.to_array();
+        let mut inv = Self::identity().to_array();
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in col + 1..4 {
+                if a[row][col].abs() > a[pivot][col].abs() {
+                    pivot = row;
+                }
+            }
+            if a[pivot][col] == 0.0 || !a[pivot][col].is_finite() {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// Applies the matrix to the point `p`, with an implicit fourth coordinate of 1.
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        let v = p.inner().replace(3, 1.0);
+        Vec3::new(
+            (self.rows[0] * v).sum(),
+            (self.rows[1] * v).sum(),
+            (self.rows[2] * v).sum(),
+        )
+    }
+
+    /// Applies the matrix to the vector `v`, with an implicit fourth coordinate of 0.
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let v = v.inner().replace(3, 0.0);
+        Vec3::new(
+            (self.rows[0] * v).sum(),
+            (self.rows[1] * v).sum(),
+            (self.rows[2] * v).sum(),
+        )
+    }
+
+    /// Applies the transpose of the matrix to the vector `v`.
+    pub fn transpose_vector(&self, v: Vec3) -> Vec3 {
+        Vec3::from_inner(v.x() * self.rows[0] + v.y() * self.rows[1] + v.z() * self.rows[2])
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let row = |r: Numx4| {
+            r.extract(0) * other.rows[0]
+                + r.extract(1) * other.rows[1]
+                + r.extract(2) * other.rows[2]
+                + r.extract(3) * other.rows[3]
+        };
+        Mat4 {
+            rows: [
+                row(self.rows[0]),
+                row(self.rows[1]),
+                row(self.rows[2]),
+                row(self.rows[3]),
+            ],
+        }
+    }
+}
@@ -1,13 +1,53 @@
-use packed_simd::*;
+//! Scalar and vector number types. Numbers are `f64`, or `f32` with the `f32` feature. The
+//! vectors come from `packed_simd` when the `simd` feature is enabled, which needs a nightly
+//! compiler, and from a portable scalar implementation otherwise.
+
+#[cfg(not(feature = "simd"))]
+mod scalar;
+
+#[cfg(not(feature = "simd"))]
+use self::scalar as lanes;
+#[cfg(feature = "simd")]
+use packed_simd as lanes;
+
+pub(crate) use self::lanes::shuffle;
 
-pub type Num = f64;
 pub type Int = i64;
 
-pub type Numx4 = f64x4;
-pub type Intx4 = i64x4;
+#[cfg(not(feature = "f32"))]
+mod precision {
+    use super::lanes::{f64x4, i64x4, m64x4};
+
+    pub type Num = f64;
+    pub type Numx4 = f64x4;
+    pub type Intx4 = i64x4;
+    pub type Maskx4 = m64x4;
+
+    pub const PI: Num = std::f64::consts::PI;
+}
+
+#[cfg(feature = "f32")]
+mod precision {
+    use super::lanes::{f32x4, i32x4, m32x4};
 
-pub const MAX_NUM: Num = std::f64::MAX;
-pub const PI: Num = std::f64::consts::PI;
+    pub type Num = f32;
+    pub type Numx4 = f32x4;
+    pub type Intx4 = i32x4;
+    pub type Maskx4 = m32x4;
+
+    pub const PI: Num = std::f32::consts::PI;
+}
+
+pub use self::precision::*;
+
+pub const MAX_NUM: Num = Num::MAX;
+
+/// A bound on the relative rounding error of `n` floating point operations, as in "Physically
+/// Based Rendering".
+pub fn gamma(n: i32) -> Num {
+    let e = n as Num * Num::EPSILON * 0.5;
+    e / (1.0 - e)
+}
 
 pub fn max(a: Num, b: Num) -> Num {
     if a < b {
@@ -0,0 +1,273 @@
+//! Portable stand-ins for the `packed_simd` vectors used by the crate, with one plain array
+//! element per lane so the crate builds on stable Rust.
+
+use std::ops::*;
+
+/// Builds a vector from the lanes of `$v` given by the indices, like `packed_simd::shuffle!`.
+macro_rules! shuffle {
+    ($v:expr, [$a:expr, $b:expr, $c:expr, $d:expr]) => {
+        $v.shuffle([$a, $b, $c, $d])
+    };
+}
+
+pub(crate) use shuffle;
+
+macro_rules! lanes {
+    ($lanes:ident, $mask:ident, $elem:ty) => {
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Copy, Clone, Default, PartialEq)]
+        #[repr(transparent)]
+        pub struct $lanes([$elem; 4]);
+
+        impl $lanes {
+            pub const fn new(a: $elem, b: $elem, c: $elem, d: $elem) -> Self {
+                $lanes([a, b, c, d])
+            }
+
+            pub const fn splat(a: $elem) -> Self {
+                $lanes([a; 4])
+            }
+
+            pub fn extract(self, lane: usize) -> $elem {
+                self.0[lane]
+            }
+
+            pub fn replace(mut self, lane: usize, value: $elem) -> Self {
+                self.0[lane] = value;
+                self
+            }
+
+            pub fn sum(self) -> $elem {
+                self.0[0] + self.0[1] + self.0[2] + self.0[3]
+            }
+
+            pub fn shuffle(self, indices: [usize; 4]) -> Self {
+                self.map_lanes(|lane| self.0[indices[lane]])
+            }
+
+            pub fn lt(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a < b))
+            }
+
+            pub fn le(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a <= b))
+            }
+
+            pub fn gt(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a > b))
+            }
+
+            pub fn ge(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a >= b))
+            }
+
+            pub fn eq(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a == b))
+            }
+
+            pub fn ne(self, other: Self) -> $mask {
+                $mask(self.zip_lanes(other, |a, b| a != b))
+            }
+
+            fn map_lanes(self, f: impl Fn(usize) -> $elem) -> Self {
+                $lanes([f(0), f(1), f(2), f(3)])
+            }
+
+            fn zip_lanes<T>(self, other: Self, f: impl Fn($elem, $elem) -> T) -> [T; 4] {
+                [
+                    f(self.0[0], other.0[0]),
+                    f(self.0[1], other.0[1]),
+                    f(self.0[2], other.0[2]),
+                    f(self.0[3], other.0[3]),
+                ]
+            }
+        }
+
+        lanes!(@op $lanes, $elem, Add, add, AddAssign, add_assign);
+        lanes!(@op $lanes, $elem, Sub, sub, SubAssign, sub_assign);
+        lanes!(@op $lanes, $elem, Mul, mul, MulAssign, mul_assign);
+        lanes!(@op $lanes, $elem, Div, div, DivAssign, div_assign);
+    };
+    (@op $lanes:ident, $elem:ty, $trait:ident, $method:ident, $assign:ident, $assign_method:ident) => {
+        impl $trait for $lanes {
+            type Output = $lanes;
+
+            fn $method(self, other: $lanes) -> $lanes {
+                $lanes(self.zip_lanes(other, $trait::$method))
+            }
+        }
+
+        impl $trait<$elem> for $lanes {
+            type Output = $lanes;
+
+            fn $method(self, other: $elem) -> $lanes {
+                self.$method($lanes::splat(other))
+            }
+        }
+
+        impl $trait<$lanes> for $elem {
+            type Output = $lanes;
+
+            fn $method(self, other: $lanes) -> $lanes {
+                $lanes::splat(self).$method(other)
+            }
+        }
+
+        impl $assign for $lanes {
+            fn $assign_method(&mut self, other: $lanes) {
+                *self = self.$method(other);
+            }
+        }
+
+        impl $assign<$elem> for $lanes {
+            fn $assign_method(&mut self, other: $elem) {
+                *self = self.$method(other);
+            }
+        }
+    };
+}
+
+macro_rules! float_lanes {
+    ($lanes:ident, $mask:ident, $elem:ty) => {
+        lanes!($lanes, $mask, $elem);
+
+        impl $lanes {
+            pub fn abs(self) -> Self {
+                self.map_lanes(|lane| self.0[lane].abs())
+            }
+
+            pub fn sqrt(self) -> Self {
+                self.map_lanes(|lane| self.0[lane].sqrt())
+            }
+
+            /// The lane-wise minimum, ignoring NaNs like `packed_simd` does.
+            pub fn min(self, other: Self) -> Self {
+                $lanes(self.zip_lanes(other, <$elem>::min))
+            }
+
+            /// The lane-wise maximum, ignoring NaNs like `packed_simd` does.
+            pub fn max(self, other: Self) -> Self {
+                $lanes(self.zip_lanes(other, <$elem>::max))
+            }
+        }
+
+        impl Neg for $lanes {
+            type Output = $lanes;
+
+            fn neg(self) -> $lanes {
+                self.map_lanes(|lane| -self.0[lane])
+            }
+        }
+
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Copy, Clone, Default, PartialEq)]
+        pub struct $mask([bool; 4]);
+
+        impl $mask {
+            pub const fn new(a: bool, b: bool, c: bool, d: bool) -> Self {
+                $mask([a, b, c, d])
+            }
+
+            pub const fn splat(a: bool) -> Self {
+                $mask([a; 4])
+            }
+
+            pub fn extract(self, lane: usize) -> bool {
+                self.0[lane]
+            }
+
+            pub fn replace(mut self, lane: usize, value: bool) -> Self {
+                self.0[lane] = value;
+                self
+            }
+
+            pub fn all(self) -> bool {
+                self.0.iter().all(|&lane| lane)
+            }
+
+            pub fn any(self) -> bool {
+                self.0.iter().any(|&lane| lane)
+            }
+
+            pub fn none(self) -> bool {
+                !self.any()
+            }
+
+            /// One bit per lane, with the first lane in the least significant bit.
+            pub fn bitmask(self) -> u8 {
+                (0..4)
+                    .filter(|&lane| self.0[lane])
+                    .map(|lane| 1 << lane)
+                    .sum()
+            }
+
+            /// Picks the lanes of `a` where the mask is set and the lanes of `b` elsewhere.
+            pub fn select(self, a: $lanes, b: $lanes) -> $lanes {
+                a.map_lanes(|lane| if self.0[lane] { a.0[lane] } else { b.0[lane] })
+            }
+
+            fn zip_lanes(self, other: Self, f: impl Fn(bool, bool) -> bool) -> Self {
+                $mask([
+                    f(self.0[0], other.0[0]),
+                    f(self.0[1], other.0[1]),
+                    f(self.0[2], other.0[2]),
+                    f(self.0[3], other.0[3]),
+                ])
+            }
+        }
+
+        impl BitAnd for $mask {
+            type Output = $mask;
+
+            fn bitand(self, other: $mask) -> $mask {
+                self.zip_lanes(other, BitAnd::bitand)
+            }
+        }
+
+        impl BitOr for $mask {
+            type Output = $mask;
+
+            fn bitor(self, other: $mask) -> $mask {
+                self.zip_lanes(other, BitOr::bitor)
+            }
+        }
+
+        impl BitXor for $mask {
+            type Output = $mask;
+
+            fn bitxor(self, other: $mask) -> $mask {
+                self.zip_lanes(other, BitXor::bitxor)
+            }
+        }
+
+        impl Not for $mask {
+            type Output = $mask;
+
+            fn not(self) -> $mask {
+                self.zip_lanes(self, |a, _| !a)
+            }
+        }
+
+        impl BitAndAssign for $mask {
+            fn bitand_assign(&mut self, other: $mask) {
+                *self = *self & other;
+            }
+        }
+
+        impl BitOrAssign for $mask {
+            fn bitor_assign(&mut self, other: $mask) {
+                *self = *self | other;
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "f32"))]
+float_lanes!(f64x4, m64x4, f64);
+#[cfg(not(feature = "f32"))]
+lanes!(i64x4, m64x4, i64);
+
+#[cfg(feature = "f32")]
+float_lanes!(f32x4, m32x4, f32);
+#[cfg(feature = "f32")]
+lanes!(i32x4, m32x4, i32);
@@ -0,0 +1,62 @@
+use crate::num::*;
+use crate::ray::Ray;
+
+pub const PACKET_SIZE: usize = 4;
+
+/// A group of rays traced together, stored as one SIMD lane per ray.
+pub struct RayPacket {
+    rays: [Ray; PACKET_SIZE],
+    origin: [Numx4; 3],
+    direction: [Numx4; 3],
+    inv_direction: [Numx4; 3],
+}
+
+impl RayPacket {
+    pub fn new(rays: [Ray; PACKET_SIZE]) -> Self {
+        let component = |f: &dyn Fn(&Ray) -> Num| {
+            Numx4::new(f(&rays[0]), f(&rays[1]), f(&rays[2]), f(&rays[3]))
+        };
+        let origin = [
+            component(&|r| r.origin().x()),
+            component(&|r| r.origin().y()),
+            component(&|r| r.origin().z()),
+        ];
+        let direction = [
+            component(&|r| r.direction().x()),
+            component(&|r| r.direction().y()),
+            component(&|r| r.direction().z()),
+        ];
+        let inv_direction = [1.0 / direction[0], 1.0 / direction[1], 1.0 / direction[2]];
+
+        RayPacket {
+            rays,
+            origin,
+            direction,
+            inv_direction,
+        }
+    }
+
+    #[inline(always)]
+    pub fn ray(&self, lane: usize) -> &Ray {
+        &self.rays[lane]
+    }
+
+    #[inline(always)]
+    pub fn origin(&self) -> &[Numx4; 3] {
+        &self.origin
+    }
+
+    #[inline(always)]
+    pub fn direction(&self) -> &[Numx4; 3] {
+        &self.direction
+    }
+
+    #[inline(always)]
+    pub fn inv_direction(&self) -> &[Numx4; 3] {
+        &self.inv_direction
+    }
+
+    pub fn into_rays(self) -> [Ray; PACKET_SIZE] {
+        self.rays
+    }
+}
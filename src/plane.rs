@@ -0,0 +1,222 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable, Sampleable};
+use crate::material::Material;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::vec3::{Onb, Vec3};
+
+/// The distance along `r` to the plane through `point` with normal `n`, which doesn't need to be
+/// normalized. Rays starting on the plane, up to rounding errors, don't hit it so rays leaving a
+/// planar surface can't hit it again.
+pub(crate) fn intersect_plane(
+    point: Vec3,
+    n: Vec3,
+    r: &Ray,
+    t_min: Num,
+    t_max: Num,
+) -> Option<Num> {
+    let offset = point - r.origin();
+    let distance = n.dot(offset);
+    if distance.abs() <= gamma(4) * n.abs().dot(offset.abs()) {
+        return None;
+    }
+
+    let t = distance / n.dot(r.direction());
+    if t > t_min && t < t_max {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// The point `origin + a + b` and a bound on its rounding errors, where `a` and `b` are vectors
+/// parallel to a plane through `origin`.
+pub(crate) fn planar_point(origin: Vec3, a: Vec3, b: Vec3) -> (Vec3, Vec3) {
+    (
+        origin + a + b,
+        gamma(3) * (origin.abs() + a.abs() + b.abs()),
+    )
+}
+
+/// The distance along `r` to the disk of radius `radius` around `center` in the plane of
+/// `frame.u()` and `frame.v()`, and the coordinates of the hit point along them.
+pub(crate) fn intersect_disk(
+    center: Vec3,
+    frame: &Onb,
+    radius: Num,
+    r: &Ray,
+    t_min: Num,
+    t_max: Num,
+) -> Option<(Num, Num, Num)> {
+    let t = intersect_plane(center, frame.w(), r, t_min, t_max)?;
+    let local = frame.to_local(r.point_at(t) - center);
+    let (x, y) = (local.x(), local.y());
+    if x * x + y * y <= radius * radius {
+        Some((t, x, y))
+    } else {
+        None
+    }
+}
+
+/// Fills the geometry of `rec` for a hit of `r` at `t` with that disk at the coordinates `x` and
+/// `y`, facing `normal`, with the texture coordinates of a `Disk`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn record_disk(
+    rec: &mut HitRecord,
+    r: &Ray,
+    t: Num,
+    center: Vec3,
+    frame: &Onb,
+    radius: Num,
+    normal: Vec3,
+    (x, y): (Num, Num),
+) {
+    let (u, v) = (frame.u(), frame.v());
+    let (p, p_error) = planar_point(center, x * u, y * v);
+    let distance = (x * x + y * y).sqrt();
+
+    rec.t = t;
+    rec.p = p;
+    rec.p_error = p_error;
+    rec.set_normals(r, normal, normal);
+    rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+    rec.v = distance / radius;
+    rec.dpdu = 2.0 * PI * (x * v - y * u);
+    rec.dpdv = if distance > 0.0 {
+        radius / distance * (x * u + y * v)
+    } else {
+        radius * u
+    };
+}
+
+/// An infinite plane. It has no bounding box, so it must be kept out of the accelerators, next to
+/// them in a `HitableVec` for example. Its texture coordinates are the distances along two axes
+/// of the plane from `point`.
+pub struct Plane<T: Material> {
+    point: Vec3,
+    frame: Onb,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Plane<T> {}
+
+impl<T: Material> Plane<T> {
+    pub fn new(point: Vec3, normal: Vec3, material: T) -> Self {
+        Plane {
+            point,
+            frame: Onb::from_w(normal),
+            material,
+        }
+    }
+}
+
+impl<T: Material> Hitable for Plane<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let normal = self.frame.w();
+        let t = match intersect_plane(self.point, normal, r, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        let local = self.frame.to_local(r.point_at(t) - self.point);
+        let (u, v) = (self.frame.u(), self.frame.v());
+        let (p, p_error) = planar_point(self.point, local.x() * u, local.y() * v);
+
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = p_error;
+        rec.set_normals(r, normal, normal);
+        rec.u = local.x();
+        rec.v = local.y();
+        rec.dpdu = u;
+        rec.dpdv = v;
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        intersect_plane(self.point, self.frame.w(), r, t_min, t_max).is_some()
+    }
+}
+
+/// A disk. Its texture coordinates are the angle around the center, going from 0 to 1, and the
+/// distance to the center relative to the radius.
+pub struct Disk<T: Material> {
+    center: Vec3,
+    radius: Num,
+    frame: Onb,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Disk<T> {}
+
+impl<T: Material> Disk<T> {
+    pub fn new(center: Vec3, normal: Vec3, radius: Num, material: T) -> Self {
+        Disk {
+            center,
+            radius,
+            frame: Onb::from_w(normal),
+            material,
+        }
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, Num, Num)> {
+        intersect_disk(self.center, &self.frame, self.radius, r, t_min, t_max)
+    }
+}
+
+impl<T: Material> Hitable for Disk<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let (t, x, y) = match self.intersect(r, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let normal = self.frame.w();
+        record_disk(
+            rec,
+            r,
+            t,
+            self.center,
+            &self.frame,
+            self.radius,
+            normal,
+            (x, y),
+        );
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        // The extent of the disk along each axis shrinks with the alignment of its normal.
+        let n = self.frame.w();
+        let extent = self.radius
+            * Vec3::new(
+                (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+                (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+                (1.0 - n.z() * n.z()).max(0.0).sqrt(),
+            );
+        Some(BoundingBox::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl<T: Material> Sampleable for Disk<T> {
+    fn area(&self) -> Num {
+        PI * self.radius * self.radius
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        let distance = self.radius * u.sqrt();
+        let angle = 2.0 * PI * v;
+        let local = Vec3::new(distance * angle.cos(), distance * angle.sin(), 0.0);
+        (self.center + self.frame.to_world(local), self.frame.w())
+    }
+}
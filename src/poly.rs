@@ -0,0 +1,175 @@
+//! Real roots of polynomials up to degree four, after "Solving Quartics and Cubics for Graphics"
+//! by Jochen Schwarze in "Graphics Gems".
+// The roots are always found in `f64`, whatever the precision of `Num`, as the closed forms lose
+// too much in single precision.
+#![allow(clippy::unnecessary_cast)]
+
+use crate::num::*;
+
+use std::f64::consts::PI;
+use std::ops::Deref;
+
+/// Up to four real roots, sorted in increasing order.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Roots<T = Num> {
+    roots: [T; 4],
+    len: usize,
+}
+
+impl Roots<f64> {
+    /// Adds `root`, unless degenerate coefficients made it infinite or NaN.
+    fn push(&mut self, root: f64) {
+        if root.is_finite() {
+            self.roots[self.len] = root;
+            self.len += 1;
+        }
+    }
+
+    fn shift(mut self, offset: f64) -> Self {
+        for root in &mut self.roots[..self.len] {
+            *root += offset;
+        }
+        self
+    }
+
+    fn sorted(mut self) -> Self {
+        self.roots[..self.len].sort_by(|a, b| a.partial_cmp(b).unwrap());
+        self
+    }
+
+    fn narrow(self) -> Roots {
+        let mut roots = Roots::default();
+        for (narrow, &root) in roots.roots.iter_mut().zip(self.iter()) {
+            *narrow = root as Num;
+        }
+        roots.len = self.len;
+        roots
+    }
+}
+
+impl<T> Deref for Roots<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.roots[..self.len]
+    }
+}
+
+/// Whether `x`, the result of adding terms up to `magnitude`, is zero up to rounding errors.
+fn is_zero(x: f64, magnitude: f64) -> bool {
+    x.abs() <= f64::EPSILON.sqrt() * magnitude
+}
+
+/// The roots of `a * x² + 2 * b * x + c` given its discriminant `b² - a * c`, which must not be
+/// negative, in no particular order. Computing each root from the other avoids the cancellation
+/// in `-b ± discriminant.sqrt()`.
+pub(crate) fn quadratic_roots(a: Num, b: Num, c: Num, discriminant: Num) -> (Num, Num) {
+    let q = -(b + discriminant.sqrt().copysign(b));
+    (c / q, q / a)
+}
+
+/// The roots of `x⁴ + a * x³ + b * x² + c * x + d`, each refined with Newton's method.
+pub fn solve_quartic(a: Num, b: Num, c: Num, d: Num) -> Roots {
+    quartic(a as f64, b as f64, c as f64, d as f64).narrow()
+}
+
+fn quadratic(b: f64, c: f64) -> Roots<f64> {
+    let mut roots = Roots::default();
+    let p = 0.5 * b;
+    let discriminant = p * p - c;
+    // Missing a double root only loses grazing hits, so the rounding errors are kept tight here.
+    if discriminant.abs() <= 4.0 * f64::EPSILON * (p * p + c.abs()) {
+        roots.push(-p);
+    } else if discriminant > 0.0 {
+        // The same as `quadratic_roots`, in double precision.
+        let q = -(p + discriminant.sqrt().copysign(p));
+        roots.push(q);
+        roots.push(c / q);
+    }
+    roots.sorted()
+}
+
+fn cubic(a: f64, b: f64, c: f64) -> Roots<f64> {
+    let mut roots = Roots::default();
+
+    // Substituting `x = y - a / 3` leaves `y³ + 3 * p * y + 2 * q`.
+    let a2 = a * a;
+    let p = (b - a2 / 3.0) / 3.0;
+    let q = (2.0 / 27.0 * a * a2 - a * b / 3.0 + c) / 2.0;
+    let p3 = p * p * p;
+    let discriminant = q * q + p3;
+
+    if is_zero(discriminant, q * q + p3.abs()) {
+        let u = (-q).cbrt();
+        roots.push(2.0 * u);
+        roots.push(-u);
+    } else if discriminant < 0.0 {
+        // Three real roots.
+        let phi = (-q / (-p3).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        roots.push(t * phi.cos());
+        roots.push(-t * (phi + PI / 3.0).cos());
+        roots.push(-t * (phi - PI / 3.0).cos());
+    } else {
+        let sqrt = discriminant.sqrt();
+        roots.push((sqrt - q).cbrt() - (sqrt + q).cbrt());
+    }
+
+    roots.shift(-a / 3.0).sorted()
+}
+
+fn quartic(a: f64, b: f64, c: f64, d: f64) -> Roots<f64> {
+    let mut roots = Roots::default();
+
+    // Substituting `x = y - a / 4` leaves `y⁴ + p * y² + q * y + r`.
+    let a2 = a * a;
+    let p = -3.0 / 8.0 * a2 + b;
+    let q = a2 * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * a2 * a2 + a2 * b / 16.0 - a * c / 4.0 + d;
+
+    // Splits the quartic into two quadratics with the largest root of the resolvent cubic.
+    let resolvent = cubic(-0.5 * p, -r, 0.5 * r * p - 0.125 * q * q);
+    let z = match resolvent.last() {
+        Some(&z) => z,
+        None => return roots,
+    };
+    let u = z * z - r;
+    let v = 2.0 * z - p;
+    let u = if is_zero(u, z * z + r.abs()) {
+        0.0
+    } else if u > 0.0 {
+        u.sqrt()
+    } else {
+        return roots;
+    };
+    let v = if is_zero(v, 2.0 * z.abs() + p.abs()) {
+        0.0
+    } else if v > 0.0 {
+        v.sqrt()
+    } else {
+        return roots;
+    };
+    let v = v.copysign(q);
+    for &y in quadratic(v, z - u).iter() {
+        roots.push(y);
+    }
+    for &y in quadratic(-v, z + u).iter() {
+        roots.push(y);
+    }
+
+    let f = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+    let df = |x: f64| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+    for root in &mut roots.roots[..roots.len] {
+        let mut x = *root - a / 4.0;
+        for _ in 0..2 {
+            // Near double roots the step may overshoot, so it's only taken if it helps.
+            let next = x - f(x) / df(x);
+            if !next.is_finite() || f(next).abs() >= f(x).abs() {
+                break;
+            }
+            x = next;
+        }
+        *root = x;
+    }
+    roots.sorted()
+}
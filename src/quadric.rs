@@ -0,0 +1,419 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::num::*;
+use crate::plane::{intersect_disk, record_disk};
+use crate::poly::quadratic_roots;
+use crate::ray::Ray;
+use crate::vec3::{Onb, Vec3};
+
+/// A point and an orthonormal basis placing a shape modeled around the `z` axis in world space.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Frame {
+    origin: Vec3,
+    onb: Onb,
+}
+
+impl Frame {
+    pub(crate) fn new(origin: Vec3, axis: Vec3) -> Self {
+        Frame {
+            origin,
+            onb: Onb::from_w(axis),
+        }
+    }
+
+    pub(crate) fn origin(&self) -> Vec3 {
+        self.origin
+    }
+
+    pub(crate) fn axis(&self) -> Vec3 {
+        self.onb.w()
+    }
+
+    pub(crate) fn onb(&self) -> &Onb {
+        &self.onb
+    }
+
+    /// The origin and direction of `r` in the frame, and the sum of the absolute coordinates of
+    /// its origin relative to the frame, which bounds the errors of moving it there.
+    pub(crate) fn ray_to_local(&self, r: &Ray) -> (Vec3, Vec3, Num) {
+        let offset = r.origin() - self.origin;
+        let magnitude = offset.abs();
+        (
+            self.onb.to_local(offset),
+            self.onb.to_local(r.direction()),
+            magnitude.x() + magnitude.y() + magnitude.z(),
+        )
+    }
+
+    /// Moves the point `local`, with errors up to `local_error`, to world space and bounds its
+    /// errors there.
+    pub(crate) fn point_to_world(&self, local: Vec3, local_error: Vec3) -> (Vec3, Vec3) {
+        let (u, v, w) = (self.onb.u(), self.onb.v(), self.onb.w());
+        let (a, b, c) = (local.x() * u, local.y() * v, local.z() * w);
+        let error = gamma(6) * (self.origin.abs() + a.abs() + b.abs() + c.abs())
+            + (1.0 + gamma(6))
+                * (local_error.x() * u.abs()
+                    + local_error.y() * v.abs()
+                    + local_error.z() * w.abs());
+        (self.origin + a + b + c, error)
+    }
+
+    pub(crate) fn vector_to_world(&self, local: Vec3) -> Vec3 {
+        self.onb.to_world(local)
+    }
+
+    /// The world space box around the local box between `lo` and `hi`.
+    pub(crate) fn bounding_box(&self, lo: Vec3, hi: Vec3) -> BoundingBox {
+        let mut a = Vec3::from_scalar(MAX_NUM);
+        let mut b = Vec3::from_scalar(-MAX_NUM);
+        for corner in 0..8 {
+            let local = Vec3::new(
+                if corner & 1 == 0 { lo.x() } else { hi.x() },
+                if corner & 2 == 0 { lo.y() } else { hi.y() },
+                if corner & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            let p = self.origin + self.onb.to_world(local);
+            a = a.min(&p);
+            b = b.max(&p);
+        }
+        BoundingBox::new(a, b)
+    }
+}
+
+/// The squared distance to the axis of a surface of revolution as a polynomial of the height
+/// `z`, written so it can be evaluated without cancellations.
+#[derive(Debug, Copy, Clone)]
+enum Profile {
+    Cylinder {
+        radius: Num,
+    },
+    /// The cone with apex at `height` and slope `k`.
+    Cone {
+        k: Num,
+        height: Num,
+    },
+    /// `s * z`.
+    Paraboloid {
+        s: Num,
+    },
+    /// `r2 + s * z * z`.
+    Hyperboloid {
+        r2: Num,
+        s: Num,
+    },
+}
+
+impl Profile {
+    fn rho2(&self, z: Num) -> Num {
+        match *self {
+            Profile::Cylinder { radius } => radius * radius,
+            Profile::Cone { k, height } => {
+                let rho = k * (height - z);
+                rho * rho
+            }
+            Profile::Paraboloid { s } => s * z,
+            Profile::Hyperboloid { r2, s } => r2 + s * z * z,
+        }
+    }
+
+    /// The sum of the absolute terms of `rho2`, which bounds its rounding errors.
+    fn rho2_magnitude(&self, z: Num) -> Num {
+        match *self {
+            Profile::Hyperboloid { r2, s } => r2 + s.abs() * z * z,
+            _ => self.rho2(z).abs(),
+        }
+    }
+
+    /// Half the derivative of `rho2`.
+    fn half_slope(&self, z: Num) -> Num {
+        match *self {
+            Profile::Cylinder { .. } => 0.0,
+            Profile::Cone { k, height } => -k * k * (height - z),
+            Profile::Paraboloid { s } => 0.5 * s,
+            Profile::Hyperboloid { s, .. } => s * z,
+        }
+    }
+
+    /// The coefficient of `z * z` in `rho2`.
+    fn curvature(&self) -> Num {
+        match *self {
+            Profile::Cone { k, .. } => k * k,
+            Profile::Hyperboloid { s, .. } => s,
+            _ => 0.0,
+        }
+    }
+}
+
+/// A surface of revolution whose distance to its axis is at most quadratic in the height: a
+/// cylinder, a cone, a paraboloid or a hyperboloid, cut between two heights. Its texture
+/// coordinates are the angle around the axis, going from 0 to 1, and the height relative to the
+/// cut. The ends are open, `with_caps` closes them.
+pub struct Quadric<T: Material> {
+    frame: Frame,
+    profile: Profile,
+    z0: Num,
+    z1: Num,
+    caps: bool,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Quadric<T> {}
+
+impl<T: Material> Quadric<T> {
+    fn new(frame: Frame, profile: Profile, z0: Num, z1: Num, material: T) -> Self {
+        Quadric {
+            frame,
+            profile,
+            z0,
+            z1,
+            caps: false,
+            material,
+        }
+    }
+
+    /// The cylinder going from `base` to `base + height * axis`.
+    pub fn cylinder(base: Vec3, axis: Vec3, radius: Num, height: Num, material: T) -> Self {
+        let profile = Profile::Cylinder { radius };
+        Self::new(Frame::new(base, axis), profile, 0.0, height, material)
+    }
+
+    /// The cone with its base at `base` and its apex at `base + height * axis`.
+    pub fn cone(base: Vec3, axis: Vec3, radius: Num, height: Num, material: T) -> Self {
+        let profile = Profile::Cone {
+            k: radius / height,
+            height,
+        };
+        Self::new(Frame::new(base, axis), profile, 0.0, height, material)
+    }
+
+    /// The paraboloid with its apex at `apex`, opening along `axis` up to `radius` at `height`.
+    pub fn paraboloid(apex: Vec3, axis: Vec3, radius: Num, height: Num, material: T) -> Self {
+        let profile = Profile::Paraboloid {
+            s: radius * radius / height,
+        };
+        Self::new(Frame::new(apex, axis), profile, 0.0, height, material)
+    }
+
+    /// The hyperboloid of one sheet centered at `center`, with radius `waist` there and `radius`
+    /// at a distance `half_height` along `axis` on both sides.
+    pub fn hyperboloid(
+        center: Vec3,
+        axis: Vec3,
+        waist: Num,
+        radius: Num,
+        half_height: Num,
+        material: T,
+    ) -> Self {
+        let profile = Profile::Hyperboloid {
+            r2: waist * waist,
+            s: (radius * radius - waist * waist) / (half_height * half_height),
+        };
+        let frame = Frame::new(center, axis);
+        Self::new(frame, profile, -half_height, half_height, material)
+    }
+
+    /// Closes the ends with disks, making a solid.
+    pub fn with_caps(mut self) -> CappedQuadric<T> {
+        self.caps = true;
+        CappedQuadric(self)
+    }
+
+    /// The distance along `r` to the curved surface.
+    fn intersect_side(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<Num> {
+        let (o, d, magnitude) = self.frame.ray_to_local(r);
+        let profile = &self.profile;
+
+        // Solves `x² + y² = rho2(z)` along the ray, expanded around its origin.
+        let half_slope = profile.half_slope(o.z());
+        let a = d.x() * d.x() + d.y() * d.y() - profile.curvature() * d.z() * d.z();
+        let b = o.x() * d.x() + o.y() * d.y() - half_slope * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - profile.rho2(o.z());
+
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let (t0, t1) = quadratic_roots(a, b, c, discriminant);
+
+        // When the origin is on the surface up to rounding errors, including the ones made moving
+        // it to the frame, `t0` is the origin itself.
+        let bound =
+            magnitude * magnitude + profile.rho2_magnitude(o.z()) + half_slope.abs() * magnitude;
+        let roots = if c.abs() <= gamma(16) * bound {
+            [t1, t1]
+        } else if t0 < t1 {
+            [t0, t1]
+        } else {
+            [t1, t0]
+        };
+
+        roots.iter().cloned().find(|&t| {
+            let z = o.z() + t * d.z();
+            t > t_min && t < t_max && z >= self.z0 && z <= self.z1
+        })
+    }
+
+    /// The height, radius and outward normal in the frame of the cap closing the bottom or top
+    /// end, if any.
+    fn cap(&self, top: bool) -> Option<(Num, Num, Num)> {
+        let (z, side) = if top { (self.z1, 1.0) } else { (self.z0, -1.0) };
+        let rho = max(self.profile.rho2(z), 0.0).sqrt();
+        if self.caps && rho > 0.0 {
+            Some((z, rho, side))
+        } else {
+            None
+        }
+    }
+
+    /// The distance along `r` to the cap at height `z` with radius `rho`, and the coordinates of
+    /// the hit point on it.
+    fn intersect_cap(
+        &self,
+        z: Num,
+        rho: Num,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+    ) -> Option<(Num, Num, Num)> {
+        let center = self.frame.origin() + z * self.frame.axis();
+        intersect_disk(center, self.frame.onb(), rho, r, t_min, t_max)
+    }
+
+    /// Fills `rec` for a hit of `r` at `t` with the curved surface.
+    fn record_side<'a>(&'a self, r: &Ray, t: Num, rec: &mut HitRecord<'a>) {
+        let (o, d, _) = self.frame.ray_to_local(r);
+        let hit = o + t * d;
+        let z = hit.z();
+
+        // Projecting the hit point back on the surface at its height bounds its error
+        // independently of the ray.
+        let rho2 = max(self.profile.rho2(z), 0.0);
+        let rho = rho2.sqrt();
+        let distance = (hit.x() * hit.x() + hit.y() * hit.y()).sqrt();
+        let (x, y) = if distance > 0.0 {
+            (hit.x() * (rho / distance), hit.y() * (rho / distance))
+        } else {
+            (rho, 0.0)
+        };
+        let rho_error = if rho > 0.0 {
+            gamma(4) * self.profile.rho2_magnitude(z) / rho
+        } else {
+            0.0
+        };
+        let local = Vec3::new(x, y, z);
+        let local_error = Vec3::new(
+            gamma(5) * x.abs() + rho_error,
+            gamma(5) * y.abs() + rho_error,
+            0.0,
+        );
+        let (p, p_error) = self.frame.point_to_world(local, local_error);
+
+        let half_slope = self.profile.half_slope(z);
+        let gradient = Vec3::new(x, y, -half_slope);
+        let normal = if gradient.len_squared() > 0.0 {
+            self.frame.vector_to_world(gradient.unit())
+        } else {
+            self.frame.axis()
+        };
+
+        let height = self.z1 - self.z0;
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = p_error;
+        rec.set_normals(r, normal, normal);
+        rec.u = (y.atan2(x) + PI) / (2.0 * PI);
+        rec.v = (z - self.z0) / height;
+        rec.dpdu = self.frame.vector_to_world(2.0 * PI * Vec3::new(-y, x, 0.0));
+        rec.dpdv = if rho2 > 0.0 {
+            let slope = half_slope / rho2;
+            self.frame
+                .vector_to_world(height * Vec3::new(x * slope, y * slope, 1.0))
+        } else {
+            height * self.frame.axis()
+        };
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+    }
+
+    /// Fills `rec` for a hit of `r` at `t` with a cap, like a `Disk`.
+    fn record_cap<'a>(
+        &'a self,
+        r: &Ray,
+        t: Num,
+        (z, rho, side): (Num, Num, Num),
+        x: Num,
+        y: Num,
+        rec: &mut HitRecord<'a>,
+    ) {
+        let onb = self.frame.onb();
+        let center = self.frame.origin() + z * onb.w();
+        record_disk(rec, r, t, center, onb, rho, side * onb.w(), (x, y));
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+    }
+}
+
+impl<T: Material> Hitable for Quadric<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, mut t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let side = self.intersect_side(r, t_min, t_max);
+        if let Some(t) = side {
+            t_max = t;
+        }
+
+        let mut closest_cap = None;
+        for cap in [false, true].iter().filter_map(|&top| self.cap(top)) {
+            if let Some((t, x, y)) = self.intersect_cap(cap.0, cap.1, r, t_min, t_max) {
+                t_max = t;
+                closest_cap = Some((t, cap, x, y));
+            }
+        }
+
+        match (closest_cap, side) {
+            (Some((t, cap, x, y)), _) => self.record_cap(r, t, cap, x, y, rec),
+            (None, Some(t)) => self.record_side(r, t, rec),
+            (None, None) => return false,
+        }
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect_side(r, t_min, t_max).is_some()
+            || [false, true]
+                .iter()
+                .filter_map(|&top| self.cap(top))
+                .any(|(z, rho, _)| self.intersect_cap(z, rho, r, t_min, t_max).is_some())
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        // The radius is largest at one of the ends, or at the middle of a squeezed hyperboloid.
+        let rho = [self.z0, self.z1, max(self.z0, min(0.0, self.z1))]
+            .iter()
+            .map(|&z| max(self.profile.rho2(z), 0.0).sqrt())
+            .fold(0.0, max);
+        Some(
+            self.frame
+                .bounding_box(Vec3::new(-rho, -rho, self.z0), Vec3::new(rho, rho, self.z1)),
+        )
+    }
+}
+
+/// A `Quadric` with its ends closed by disks, which makes it a solid.
+pub struct CappedQuadric<T: Material>(Quadric<T>);
+
+impl<T: Material> Hitable for CappedQuadric<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.0.hit(r, t_min, t_max, rec)
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.0.occluded(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        self.0.bounding_box(t0, t1)
+    }
+}
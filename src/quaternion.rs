@@ -0,0 +1,185 @@
+use crate::matrix::Mat3;
+use crate::num::*;
+use crate::vec3::Vec3;
+
+use std::ops::{Mul, Neg};
+
+/// A quaternion `x i + y j + z k + w`, used to represent and interpolate rotations.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    inner: Numx4,
+}
+
+impl Quaternion {
+    pub fn new(x: Num, y: Num, z: Num, w: Num) -> Self {
+        Quaternion {
+            inner: Numx4::new(x, y, z, w),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// The rotation of `angle` degrees around `axis`, like `Transform::rotate`.
+    pub fn from_axis_angle(axis: Vec3, angle: Num) -> Self {
+        let half = 0.5 * angle * PI / 180.0;
+        let v = half.sin() * axis.unit();
+        Self::new(v.x(), v.y(), v.z(), half.cos())
+    }
+
+    /// The rotation of the rotation matrix `m`.
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+                0.25 * s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Self::new(
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[2][1] - m[1][2]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Self::new(
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s,
+                (m[0][2] - m[2][0]) / s,
+            )
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Self::new(
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        }
+    }
+
+    pub fn x(&self) -> Num {
+        self.inner.extract(0)
+    }
+
+    pub fn y(&self) -> Num {
+        self.inner.extract(1)
+    }
+
+    pub fn z(&self) -> Num {
+        self.inner.extract(2)
+    }
+
+    pub fn w(&self) -> Num {
+        self.inner.extract(3)
+    }
+
+    /// The imaginary part.
+    pub fn vector(&self) -> Vec3 {
+        Vec3::new(self.x(), self.y(), self.z())
+    }
+
+    pub fn dot(self, other: Self) -> Num {
+        (self.inner * other.inner).sum()
+    }
+
+    pub fn len(self) -> Num {
+        self.dot(self).sqrt()
+    }
+
+    pub fn unit(self) -> Self {
+        Quaternion {
+            inner: self.inner / self.len(),
+        }
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self::new(-self.x(), -self.y(), -self.z(), self.w())
+    }
+
+    /// Rotates `v` by the unit quaternion.
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let u = self.vector();
+        let w = self.w();
+        2.0 * u.dot(v) * u + (w * w - u.dot(u)) * v + 2.0 * w * u.cross(v)
+    }
+
+    /// The rotation matrix of the unit quaternion.
+    pub fn to_mat3(self) -> Mat3 {
+        let (x, y, z, w) = (self.x(), self.y(), self.z(), self.w());
+        Mat3::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    /// Spherical linear interpolation between two unit quaternions along the shortest arc.
+    pub fn slerp(self, other: Self, t: Num) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        if cos < 0.0 {
+            cos = -cos;
+            other = -other;
+        }
+
+        let (wa, wb) = if cos > 0.9995 {
+            // The rotations are too close for the angle to be accurate.
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quaternion {
+            inner: wa * self.inner + wb * other.inner,
+        }
+        .unit()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        Quaternion { inner: -self.inner }
+    }
+}
+
+/// The Hamilton product, rotating by `other` and then by `self`.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Quaternion) -> Quaternion {
+        let (a, b) = (self.vector(), other.vector());
+        let v = self.w() * b + other.w() * a + a.cross(b);
+        Self::new(v.x(), v.y(), v.z(), self.w() * other.w() - a.dot(b))
+    }
+}
@@ -5,11 +5,21 @@ use crate::vec3::Vec3;
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    time: Num,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    /// A ray cast at `time`, which moving objects use to know where they are.
+    pub fn with_time(origin: Vec3, direction: Vec3, time: Num) -> Self {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn point_at(&self, t: Num) -> Vec3 {
@@ -25,4 +35,9 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.direction
     }
+
+    #[inline(always)]
+    pub fn time(&self) -> Num {
+        self.time
+    }
 }
@@ -0,0 +1,219 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable, Sampleable};
+use crate::material::Material;
+use crate::num::*;
+use crate::plane::{intersect_plane, planar_point};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+/// A rectangle perpendicular to one of the axes, facing towards its positive side. Its texture
+/// coordinates go from 0 to 1 along the next two axes after its normal, in cyclic order.
+pub struct AxisRect<T: Material> {
+    axis: usize,
+    k: Num,
+    lo: [Num; 2],
+    hi: [Num; 2],
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for AxisRect<T> {}
+
+impl<T: Material> AxisRect<T> {
+    fn new(axis: usize, k: Num, lo: [Num; 2], hi: [Num; 2], material: T) -> Self {
+        AxisRect {
+            axis,
+            k,
+            lo: [lo[0].min(hi[0]), lo[1].min(hi[1])],
+            hi: [lo[0].max(hi[0]), lo[1].max(hi[1])],
+            material,
+        }
+    }
+
+    /// The rectangle at `z = k` between `x0` and `x1` and `y0` and `y1`.
+    pub fn xy(x0: Num, x1: Num, y0: Num, y1: Num, k: Num, material: T) -> Self {
+        Self::new(2, k, [x0, y0], [x1, y1], material)
+    }
+
+    /// The rectangle at `y = k` between `x0` and `x1` and `z0` and `z1`.
+    pub fn xz(x0: Num, x1: Num, z0: Num, z1: Num, k: Num, material: T) -> Self {
+        Self::new(1, k, [z0, x0], [z1, x1], material)
+    }
+
+    /// The rectangle at `x = k` between `y0` and `y1` and `z0` and `z1`.
+    pub fn yz(y0: Num, y1: Num, z0: Num, z1: Num, k: Num, material: T) -> Self {
+        Self::new(0, k, [y0, z0], [y1, z1], material)
+    }
+
+    fn axes(&self) -> (usize, usize) {
+        ((self.axis + 1) % 3, (self.axis + 2) % 3)
+    }
+
+    fn normal(&self) -> Vec3 {
+        let mut normal = Vec3::default();
+        normal[self.axis] = 1.0;
+        normal
+    }
+
+    fn point(&self, a: Num, b: Num) -> Vec3 {
+        let (a_axis, b_axis) = self.axes();
+        let mut p = Vec3::default();
+        p[self.axis] = self.k;
+        p[a_axis] = a;
+        p[b_axis] = b;
+        p
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, Num, Num)> {
+        // Rays starting on the rectangle get `t = 0` here, so they can't hit it again.
+        let t = (self.k - r.origin()[self.axis]) / r.direction()[self.axis];
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let (a_axis, b_axis) = self.axes();
+        let p = r.point_at(t);
+        let (a, b) = (p[a_axis], p[b_axis]);
+        if a < self.lo[0] || a > self.hi[0] || b < self.lo[1] || b > self.hi[1] {
+            return None;
+        }
+        Some((t, a, b))
+    }
+}
+
+impl<T: Material> Hitable for AxisRect<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let (t, a, b) = match self.intersect(r, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let (a_axis, b_axis) = self.axes();
+        let normal = self.normal();
+        let size = [self.hi[0] - self.lo[0], self.hi[1] - self.lo[1]];
+
+        rec.t = t;
+        // The hit point lies exactly on the plane of the rectangle, and errors along the plane
+        // don't matter to rays leaving it.
+        rec.p = self.point(a, b);
+        rec.p_error = Vec3::default();
+        rec.set_normals(r, normal, normal);
+        rec.u = (a - self.lo[0]) / size[0];
+        rec.v = (b - self.lo[1]) / size[1];
+        rec.dpdu = Vec3::default();
+        rec.dpdu[a_axis] = size[0];
+        rec.dpdv = Vec3::default();
+        rec.dpdv[b_axis] = size[1];
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(BoundingBox::new(
+            self.point(self.lo[0], self.lo[1]),
+            self.point(self.hi[0], self.hi[1]),
+        ))
+    }
+}
+
+impl<T: Material> Sampleable for AxisRect<T> {
+    fn area(&self) -> Num {
+        (self.hi[0] - self.lo[0]) * (self.hi[1] - self.lo[1])
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        let a = self.lo[0] + u * (self.hi[0] - self.lo[0]);
+        let b = self.lo[1] + v * (self.hi[1] - self.lo[1]);
+        (self.point(a, b), self.normal())
+    }
+}
+
+/// A parallelogram with a corner at `q` and sides `u` and `v`, facing along `u × v`. Its texture
+/// coordinates go from 0 to 1 along each side.
+pub struct Quad<T: Material> {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    w: Vec3,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Quad<T> {}
+
+impl<T: Material> Quad<T> {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: T) -> Self {
+        let n = u.cross(v);
+        Quad {
+            q,
+            u,
+            v,
+            normal: n.unit(),
+            w: n / n.dot(n),
+            material,
+        }
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, Num, Num)> {
+        let t = intersect_plane(self.q, self.normal, r, t_min, t_max)?;
+
+        // The coordinates of the hit point along the sides.
+        let planar = r.point_at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if (0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta) {
+            Some((t, alpha, beta))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Material> Hitable for Quad<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let (t, alpha, beta) = match self.intersect(r, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        let (p, p_error) = planar_point(self.q, alpha * self.u, beta * self.v);
+
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = p_error;
+        rec.set_normals(r, self.normal, self.normal);
+        rec.u = alpha;
+        rec.v = beta;
+        rec.dpdu = self.u;
+        rec.dpdv = self.v;
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        let (a, b) = self.q.min_max(&(self.q + self.u + self.v));
+        let (c, d) = (self.q + self.u).min_max(&(self.q + self.v));
+        Some(BoundingBox::new(a.min(&c), b.max(&d)))
+    }
+}
+
+impl<T: Material> Sampleable for Quad<T> {
+    fn area(&self) -> Num {
+        self.u.cross(self.v).len()
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        (self.q + u * self.u + v * self.v, self.normal)
+    }
+}
@@ -0,0 +1,291 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::vec3::{Onb, Vec3};
+
+/// A signed distance field: the distance to a surface, negative inside it. Fields that only
+/// bound the distance from below still work, as long as they don't overestimate it.
+pub trait Sdf: Sync {
+    fn distance(&self, p: Vec3) -> Num;
+}
+
+impl<F: Fn(Vec3) -> Num + Sync> Sdf for F {
+    fn distance(&self, p: Vec3) -> Num {
+        self(p)
+    }
+}
+
+/// A box centered at the origin, with its edges rounded by `radius`.
+pub struct RoundedBox {
+    half_size: Vec3,
+    radius: Num,
+}
+
+impl RoundedBox {
+    /// The box spanning `half_size` on each side of the origin, rounded within that size.
+    pub fn new(half_size: Vec3, radius: Num) -> Self {
+        RoundedBox {
+            half_size: half_size - Vec3::from_scalar(radius),
+            radius,
+        }
+    }
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: Vec3) -> Num {
+        let q = p.abs() - self.half_size;
+        let outside = q.max(&Vec3::default()).len();
+        let inside = min(max(q.x(), max(q.y(), q.z())), 0.0);
+        outside + inside - self.radius
+    }
+}
+
+/// The points at distance `radius` from the segment between `a` and `b`.
+pub struct Capsule {
+    a: Vec3,
+    b: Vec3,
+    radius: Num,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: Num) -> Self {
+        Capsule { a, b, radius }
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vec3) -> Num {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = max(0.0, min(pa.dot(ba) / ba.dot(ba), 1.0));
+        (pa - h * ba).len() - self.radius
+    }
+}
+
+/// A torus centered at the origin around the `z` axis.
+pub struct Torus {
+    major: Num,
+    minor: Num,
+}
+
+impl Torus {
+    pub fn new(major: Num, minor: Num) -> Self {
+        Torus { major, minor }
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Vec3) -> Num {
+        let ring = (p.x() * p.x() + p.y() * p.y()).sqrt() - self.major;
+        (ring * ring + p.z() * p.z()).sqrt() - self.minor
+    }
+}
+
+/// The union of two fields, blending them where they are closer than `k` to each other.
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    a: A,
+    b: B,
+    k: Num,
+}
+
+impl<A: Sdf, B: Sdf> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, k: Num) -> Self {
+        SmoothUnion { a, b, k }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: Vec3) -> Num {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        let h = max(0.0, min(0.5 + 0.5 * (b - a) / self.k, 1.0));
+        b + h * (a - b) - self.k * h * (1.0 - h)
+    }
+}
+
+/// The first field without the second.
+pub struct Subtraction<A: Sdf, B: Sdf> {
+    a: A,
+    b: B,
+}
+
+impl<A: Sdf, B: Sdf> Subtraction<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Subtraction { a, b }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Subtraction<A, B> {
+    fn distance(&self, p: Vec3) -> Num {
+        max(self.a.distance(p), -self.b.distance(p))
+    }
+}
+
+/// Copies of a field repeated forever every `period` along each axis, or not repeated along the
+/// axes where it's zero. The field must fit in a cell of the repetition, centered at the origin.
+pub struct Repetition<S: Sdf> {
+    inner: S,
+    period: Vec3,
+}
+
+impl<S: Sdf> Repetition<S> {
+    pub fn new(inner: S, period: Vec3) -> Self {
+        Repetition { inner, period }
+    }
+}
+
+impl<S: Sdf> Sdf for Repetition<S> {
+    fn distance(&self, mut p: Vec3) -> Num {
+        for axis in 0..3 {
+            let period = self.period[axis];
+            if period > 0.0 {
+                p[axis] -= period * (p[axis] / period).round();
+            }
+        }
+        self.inner.distance(p)
+    }
+}
+
+/// A field twisted around the `z` axis by `rate` radians per unit of height. Twisting stretches
+/// distances by up to `sqrt(1 + (rate * r)²)` at a distance `r` of the axis, so shapes using it
+/// need that bound set with `with_lipschitz`.
+pub struct Twist<S: Sdf> {
+    inner: S,
+    rate: Num,
+}
+
+impl<S: Sdf> Twist<S> {
+    pub fn new(inner: S, rate: Num) -> Self {
+        Twist { inner, rate }
+    }
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: Vec3) -> Num {
+        let angle = self.rate * p.z();
+        let (sin, cos) = angle.sin_cos();
+        let q = Vec3::new(cos * p.x() - sin * p.y(), sin * p.x() + cos * p.y(), p.z());
+        self.inner.distance(q)
+    }
+}
+
+/// The most steps taken along a ray before giving up.
+const MAX_STEPS: usize = 512;
+
+/// The surface of a signed distance field inside `bounding_box`, intersected by sphere tracing.
+/// Hits are found within a distance of the surface relative to the size of the box, so rays
+/// grazing it may hit or miss it by that much. Its normals are estimated with finite differences,
+/// and it has no texture coordinates.
+pub struct SdfShape<S: Sdf, T: Material> {
+    sdf: S,
+    bounding_box: BoundingBox,
+    lipschitz: Num,
+    /// How close to the surface a point must be to be on it.
+    epsilon: Num,
+    material: T,
+}
+
+unsafe impl<S: Sdf, T: Material> Sync for SdfShape<S, T> {}
+
+impl<S: Sdf, T: Material> SdfShape<S, T> {
+    pub fn new(sdf: S, bounding_box: BoundingBox, material: T) -> Self {
+        let (lo, hi) = (bounding_box.min(), bounding_box.max());
+        let magnitude = lo.abs().max(&hi.abs());
+        let epsilon =
+            1e-4 * ((hi - lo).len() + max(magnitude.x(), max(magnitude.y(), magnitude.z())));
+        SdfShape {
+            sdf,
+            bounding_box,
+            lipschitz: 1.0,
+            epsilon,
+            material,
+        }
+    }
+
+    /// Sets how many times faster than the distance to the surface the field can change, for
+    /// fields distorting distances like `Twist`. Steps are shortened by that much.
+    pub fn with_lipschitz(mut self, lipschitz: Num) -> Self {
+        self.lipschitz = lipschitz;
+        self
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<Num> {
+        let (mut t, end) = self.bounding_box.intersect(r, t_min, t_max)?;
+        let len = r.direction().len();
+
+        // Rays starting on the surface, like the ones leaving a hit, have to get away from it
+        // before they can hit it.
+        let mut away = t > t_min;
+        for _ in 0..MAX_STEPS {
+            let distance = self.sdf.distance(r.point_at(t)).abs() / self.lipschitz;
+            if distance < self.epsilon {
+                if away {
+                    return Some(t);
+                }
+            } else {
+                away = true;
+            }
+            t += max(distance, self.epsilon) / len;
+            if t > end {
+                break;
+            }
+        }
+        None
+    }
+
+    /// The gradient of the field at `p`, from the differences at the corners of a tetrahedron.
+    fn normal(&self, p: Vec3) -> Vec3 {
+        let h = self.epsilon;
+        let gradient = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .iter()
+        .fold(Vec3::default(), |sum, &corner| {
+            sum + self.sdf.distance(p + h * corner) * corner
+        });
+        if gradient.len_squared() > 0.0 {
+            gradient.unit()
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        }
+    }
+}
+
+impl<S: Sdf, T: Material> Hitable for SdfShape<S, T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let t = match self.intersect(r, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        let p = r.point_at(t);
+        let normal = self.normal(p);
+        let frame = Onb::from_w(normal);
+
+        rec.t = t;
+        rec.p = p;
+        // The hit point is anywhere within `epsilon` of the surface.
+        rec.p_error = Vec3::from_scalar(self.epsilon);
+        rec.set_normals(r, normal, normal);
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.dpdu = frame.u();
+        rec.dpdv = frame.v();
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(self.bounding_box.clone())
+    }
+}
@@ -1,9 +1,13 @@
 use crate::bounding_box::BoundingBox;
-use crate::hitable::{HitRecord, Hitable};
+use crate::hitable::{area_pdf, HitRecord, Hitable, Sampleable};
 use crate::material::Material;
 use crate::num::*;
+use crate::packet::{RayPacket, PACKET_SIZE};
+use crate::poly::quadratic_roots;
 use crate::ray::Ray;
-use crate::vec3::Vec3;
+use crate::vec3::{Onb, Vec3};
+
+use rand::prelude::{Rng, ThreadRng};
 
 pub struct Sphere<T: Material> {
     center: Vec3,
@@ -23,35 +27,152 @@ impl<T: Material> Sphere<T> {
     }
 }
 
+impl<T: Material> Sphere<T> {
+    fn record<'a>(&'a self, r: &Ray, t: Num, rec: &mut HitRecord<'a>) {
+        record(self.center, self.radius, r, t, rec);
+        rec.material = &self.material;
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<Num> {
+        intersect(self.center, self.radius, r, t_min, t_max)
+    }
+}
+
+/// Fills `rec` for a hit of `r` at `t` with the sphere at `center`, except for the material. The
+/// texture coordinates are the longitude and latitude of the hit point, with `v` going from the
+/// bottom to the top of the sphere.
+fn record(center: Vec3, radius: Num, r: &Ray, t: Num, rec: &mut HitRecord) {
+    // Projecting the hit point back on the sphere bounds its error independently of the ray.
+    let local = r.point_at(t) - center;
+    let local = local * (radius / local.len());
+    let p = center + local;
+    let normal = local / radius;
+    let (x, y, z) = (normal.x(), normal.y(), normal.z());
+
+    rec.t = t;
+    rec.p = p;
+    rec.p_error = gamma(5) * local.abs() + gamma(1) * p.abs();
+    rec.set_normals(r, normal, normal);
+    rec.u = ((-z).atan2(x) + PI) / (2.0 * PI);
+    rec.v = max(-1.0, min(-y, 1.0)).acos() / PI;
+
+    let rho = (x * x + z * z).sqrt();
+    rec.dpdu = 2.0 * PI * radius * Vec3::new(z, 0.0, -x);
+    rec.dpdv = if rho > 0.0 {
+        PI * radius * Vec3::new(-x * y / rho, rho, -y * z / rho)
+    } else {
+        Vec3::new(0.0, 0.0, PI * radius)
+    };
+    rec.primitive_id = 0;
+    rec.instance_id = None;
+}
+
+fn intersect(center: Vec3, radius: Num, r: &Ray, t_min: Num, t_max: Num) -> Option<Num> {
+    let oc = r.origin() - center;
+    let direction = r.direction();
+
+    let a = direction.dot(direction);
+    let b = oc.dot(direction);
+    let c = oc.dot(oc) - radius * radius;
+
+    // Using the distance from the center to the ray avoids the cancellation in `b * b - a * c`.
+    let l = oc - (b / a) * direction;
+    let discriminant = a * (radius * radius - l.dot(l));
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    let (t0, t1) = quadratic_roots(a, b, c, discriminant);
+
+    // When the origin is on the surface up to rounding errors, `t0` is the origin itself.
+    let (near, far) = if c.abs() <= gamma(5) * (oc.dot(oc) + radius * radius) {
+        (t1, t1)
+    } else if t0 < t1 {
+        (t0, t1)
+    } else {
+        (t1, t0)
+    };
+
+    if near < t_max && near > t_min {
+        Some(near)
+    } else if far < t_max && far > t_min {
+        Some(far)
+    } else {
+        None
+    }
+}
+
 impl<T: Material> Hitable for Sphere<T> {
     fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
-        let oc = r.origin() - self.center;
+        match self.intersect(r, t_min, t_max) {
+            Some(temp) => {
+                self.record(r, temp, rec);
+                true
+            }
+            None => false,
+        }
+    }
 
-        let a = r.direction().dot(r.direction());
-        let b = oc.dot(r.direction());
-        let c = oc.dot(oc) - self.radius * self.radius;
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
 
-        let discriminant = b * b - a * c;
+    fn hit_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        t_min: Num,
+        t_max: &mut Numx4,
+        active: Maskx4,
+        recs: &mut [HitRecord<'a>; PACKET_SIZE],
+    ) -> Maskx4 {
+        let origin = packet.origin();
+        let direction = packet.direction();
+        let oc = [
+            origin[0] - self.center.x(),
+            origin[1] - self.center.y(),
+            origin[2] - self.center.z(),
+        ];
 
-        if discriminant > 0.0 {
-            let mut temp = (-b - discriminant.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                rec.t = temp;
-                rec.p = r.point_at(temp);
-                rec.normal = (rec.p - self.center) / self.radius;
-                rec.material = &self.material;
-                return true;
-            }
-            temp = (-b + discriminant.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                rec.t = temp;
-                rec.p = r.point_at(temp);
-                rec.normal = (rec.p - self.center) / self.radius;
-                rec.material = &self.material;
-                return true;
+        let radius2 = self.radius * self.radius;
+        let a =
+            direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2];
+        let b = oc[0] * direction[0] + oc[1] * direction[1] + oc[2] * direction[2];
+        let oc2 = oc[0] * oc[0] + oc[1] * oc[1] + oc[2] * oc[2];
+        let c = oc2 - radius2;
+
+        let l = [
+            oc[0] - b / a * direction[0],
+            oc[1] - b / a * direction[1],
+            oc[2] - b / a * direction[2],
+        ];
+        let discriminant = a * (radius2 - (l[0] * l[0] + l[1] * l[1] + l[2] * l[2]));
+        let zero = Numx4::splat(0.0);
+        let valid = active & discriminant.gt(zero);
+        if valid.none() {
+            return valid;
+        }
+
+        let root = valid.select(discriminant, zero).sqrt();
+        let q = -(b + b.lt(zero).select(-root, root));
+        let (t0, t1) = (c / q, q / a);
+        let on_surface = c.abs().le(gamma(5) * (oc2 + radius2));
+        let near = on_surface.select(t1, t0.lt(t1).select(t0, t1));
+        let far = on_surface.select(t1, t0.lt(t1).select(t1, t0));
+        let t_min = Numx4::splat(t_min);
+
+        let hit_near = valid & near.lt(*t_max) & near.gt(t_min);
+        let hit_far = valid & !hit_near & far.lt(*t_max) & far.gt(t_min);
+        let hit = hit_near | hit_far;
+        let t = hit_near.select(near, far);
+
+        for (lane, rec) in recs.iter_mut().enumerate() {
+            if hit.extract(lane) {
+                self.record(packet.ray(lane), t.extract(lane), rec);
             }
         }
-        false
+        *t_max = hit.select(t, *t_max);
+
+        hit
     }
 
     fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
@@ -61,3 +182,114 @@ impl<T: Material> Hitable for Sphere<T> {
         ))
     }
 }
+
+impl<T: Material> Sampleable for Sphere<T> {
+    fn area(&self) -> Num {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        let y = 1.0 - 2.0 * u;
+        let rho = max(0.0, 1.0 - y * y).sqrt();
+        let phi = 2.0 * PI * v;
+        let normal = Vec3::new(rho * phi.cos(), y, rho * phi.sin());
+        (self.center + self.radius * normal, normal)
+    }
+
+    /// Samples the cone of directions towards the sphere from outside of it, which wastes no
+    /// samples on its hidden side.
+    fn sample_direction(&self, origin: Vec3, rng: &mut ThreadRng) -> Vec3 {
+        let to_center = self.center - origin;
+        let sin2_max = self.radius * self.radius / to_center.len_squared();
+        if sin2_max >= 1.0 {
+            return self.sample(rng.gen(), rng.gen()).0 - origin;
+        }
+
+        // `1 - cos` is computed from the sine so it doesn't vanish for small, distant spheres.
+        let cos_max = (1.0 - sin2_max).sqrt();
+        let one_minus_cos = rng.gen::<Num>() * sin2_max / (1.0 + cos_max);
+        let cos = 1.0 - one_minus_cos;
+        let sin = max(0.0, one_minus_cos * (1.0 + cos)).sqrt();
+        let phi = 2.0 * PI * rng.gen::<Num>();
+        Onb::from_w(to_center).to_world(Vec3::new(sin * phi.cos(), sin * phi.sin(), cos))
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Num {
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction), 0.0, MAX_NUM, &mut rec) {
+            return 0.0;
+        }
+
+        let sin2_max = self.radius * self.radius / (self.center - origin).len_squared();
+        if sin2_max >= 1.0 {
+            return area_pdf(&rec, direction, self.area());
+        }
+        let cos_max = (1.0 - sin2_max).sqrt();
+        (1.0 + cos_max) / (2.0 * PI * sin2_max)
+    }
+}
+
+/// A sphere whose center moves linearly from `center0` at `time0` to `center1` at `time1`.
+pub struct MovingSphere<T: Material> {
+    center0: Vec3,
+    center1: Vec3,
+    time0: Num,
+    time1: Num,
+    radius: Num,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for MovingSphere<T> {}
+
+impl<T: Material> MovingSphere<T> {
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: Num,
+        time1: Num,
+        radius: Num,
+        material: T,
+    ) -> Self {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: Num) -> Vec3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let t = (time - self.time0) / (self.time1 - self.time0);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+}
+
+impl<T: Material> Hitable for MovingSphere<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let center = self.center(r.time());
+        match intersect(center, self.radius, r, t_min, t_max) {
+            Some(temp) => {
+                record(center, self.radius, r, temp, rec);
+                rec.material = &self.material;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        intersect(self.center(r.time()), self.radius, r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        let (c0, c1) = (self.center(t0), self.center(t1));
+        let box0 = BoundingBox::new(c0 - self.radius, c0 + self.radius);
+        let box1 = BoundingBox::new(c1 - self.radius, c1 + self.radius);
+        Some(box0.surrounding_box(&box1))
+    }
+}
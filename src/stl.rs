@@ -1,13 +1,30 @@
 use crate::material::Material;
 use crate::num::*;
 use crate::triangle::Triangle;
+use crate::triangle_mesh::{merge_vertices, vertex_normals, TriangleMesh};
 use crate::vec3::Vec3;
 
+use rand::prelude::ThreadRng;
 use regex::Regex;
 
 use std::fs::File;
 use std::io::{BufReader, Read};
 
+/// Gives smooth vertex normals to triangles read from an STL file, which only stores facet
+/// normals. See `triangle_mesh::vertex_normals` for the meaning of `crease_angle`.
+pub fn smooth<T: Material>(triangles: Vec<Triangle<T>>, crease_angle: Num) -> Vec<Triangle<T>> {
+    let (vertices, indices) = merge_vertices(triangles.iter().map(Triangle::vertices));
+    let normals = vertex_normals(&vertices, &indices, crease_angle);
+    triangles
+        .into_iter()
+        .zip(normals)
+        .map(|(triangle, normals)| triangle.with_vertex_normals(normals))
+        .collect()
+}
+
+/// The normal and vertices of a facet.
+type Facet = (Vec3, [Vec3; 3]);
+
 fn get_u32(iter: &mut impl Iterator<Item = std::io::Result<u8>>) -> Option<u32> {
     Some(u32::from_le_bytes([
         iter.next()?.ok()?,
@@ -23,6 +40,29 @@ pub fn read_bin<T: Material + Clone>(
     offset: Vec3,
     material: T,
 ) -> std::io::Result<Vec<Triangle<T>>> {
+    Ok(read_bin_facets(path, scale, offset)?
+        .into_iter()
+        .map(|(n, [p1, p2, p3])| Triangle::new(n, p1, p2, p3, material.clone()))
+        .collect())
+}
+
+/// Reads a binary STL file into a `TriangleMesh`, merging the vertices shared by its facets.
+pub fn read_bin_mesh<T: Material>(
+    path: &str,
+    scale: Vec3,
+    offset: Vec3,
+    material: T,
+    rng: &mut ThreadRng,
+) -> std::io::Result<TriangleMesh<T>> {
+    let facets = read_bin_facets(path, scale, offset)?;
+    Ok(TriangleMesh::from_triangles(
+        facets.into_iter().map(|(_, points)| points),
+        material,
+        rng,
+    ))
+}
+
+fn read_bin_facets(path: &str, scale: Vec3, offset: Vec3) -> std::io::Result<Vec<Facet>> {
     let file = BufReader::new(File::open(path)?);
     let mut bytes = file.bytes().skip(80);
 
@@ -49,12 +89,13 @@ pub fn read_bin<T: Material + Clone>(
             bytes.next().unwrap().unwrap();
             bytes.next().unwrap().unwrap();
 
-            Triangle::new(
+            (
                 (Vec3::new(n1, n2, n3) * scale).unit(),
-                Vec3::new(a1, a2, a3) * scale + offset,
-                Vec3::new(b1, b2, b3) * scale + offset,
-                Vec3::new(c1, c2, c3) * scale + offset,
-                material.clone(),
+                [
+                    Vec3::new(a1, a2, a3) * scale + offset,
+                    Vec3::new(b1, b2, b3) * scale + offset,
+                    Vec3::new(c1, c2, c3) * scale + offset,
+                ],
             )
         })
         .collect())
@@ -66,6 +107,29 @@ pub fn read<T: Material + Clone>(
     offset: Vec3,
     material: T,
 ) -> std::io::Result<Vec<Triangle<T>>> {
+    Ok(read_facets(path, scale, offset)?
+        .into_iter()
+        .map(|(n, [p1, p2, p3])| Triangle::new(n, p1, p2, p3, material.clone()))
+        .collect())
+}
+
+/// Reads an ASCII STL file into a `TriangleMesh`, merging the vertices shared by its facets.
+pub fn read_mesh<T: Material>(
+    path: &str,
+    scale: Num,
+    offset: Vec3,
+    material: T,
+    rng: &mut ThreadRng,
+) -> std::io::Result<TriangleMesh<T>> {
+    let facets = read_facets(path, scale, offset)?;
+    Ok(TriangleMesh::from_triangles(
+        facets.into_iter().map(|(_, points)| points),
+        material,
+        rng,
+    ))
+}
+
+fn read_facets(path: &str, scale: Num, offset: Vec3) -> std::io::Result<Vec<Facet>> {
     let mut file = BufReader::new(File::open(path)?);
     let mut buffer = String::new();
     file.read_to_string(&mut buffer)?;
@@ -107,7 +171,7 @@ pub fn read<T: Material + Clone>(
             ) * scale
                 + offset;
 
-            Triangle::new(n, p1, p2, p3, material.clone())
+            (n, [p1, p2, p3])
         })
         .collect::<Vec<_>>())
 }
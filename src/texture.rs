@@ -0,0 +1,113 @@
+use crate::num::Num;
+use crate::vec3::Vec3;
+
+mod image;
+mod noise;
+
+pub use self::image::{Filter, ImageTexture, Wrap};
+pub use self::noise::{
+    fbm, turbulence, Fbm, Marble, Noise, Perlin, Simplex, Turbulence, Wood, Worley,
+};
+
+/// A color that varies over a surface, evaluated at the texture coordinates `u` and `v` of a hit
+/// and at its position `p`.
+///
+/// Scalar parameters such as the roughness of a material use the first channel of the texture.
+pub trait Texture {
+    fn value(&self, u: Num, v: Num, p: Vec3) -> Vec3;
+}
+
+impl Texture for Num {
+    fn value(&self, _: Num, _: Num, _: Vec3) -> Vec3 {
+        Vec3::from_scalar(*self)
+    }
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(r: Num, g: Num, b: Num) -> Self {
+        SolidColor {
+            color: Vec3::new(r, g, b),
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _: Num, _: Num, _: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// Alternates between two textures in a checkerboard pattern with `frequency` squares per unit
+/// of texture coordinates.
+#[derive(Clone)]
+pub struct Checker<A: Texture, B: Texture> {
+    even: A,
+    odd: B,
+    frequency: Num,
+}
+
+impl<A: Texture, B: Texture> Checker<A, B> {
+    pub fn new(even: A, odd: B, frequency: Num) -> Self {
+        Checker {
+            even,
+            odd,
+            frequency,
+        }
+    }
+}
+
+impl<A: Texture, B: Texture> Texture for Checker<A, B> {
+    fn value(&self, u: Num, v: Num, p: Vec3) -> Vec3 {
+        let square = (u * self.frequency).floor() + (v * self.frequency).floor();
+        if square.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Multiplies a texture by another one.
+#[derive(Clone)]
+pub struct Scale<T: Texture, S: Texture> {
+    texture: T,
+    scale: S,
+}
+
+impl<T: Texture, S: Texture> Scale<T, S> {
+    pub fn new(texture: T, scale: S) -> Self {
+        Scale { texture, scale }
+    }
+}
+
+impl<T: Texture, S: Texture> Texture for Scale<T, S> {
+    fn value(&self, u: Num, v: Num, p: Vec3) -> Vec3 {
+        self.texture.value(u, v, p) * self.scale.value(u, v, p)
+    }
+}
+
+/// Blends two textures, using `amount` to weight the second one.
+#[derive(Clone)]
+pub struct Mix<A: Texture, B: Texture, M: Texture> {
+    a: A,
+    b: B,
+    amount: M,
+}
+
+impl<A: Texture, B: Texture, M: Texture> Mix<A, B, M> {
+    pub fn new(a: A, b: B, amount: M) -> Self {
+        Mix { a, b, amount }
+    }
+}
+
+impl<A: Texture, B: Texture, M: Texture> Texture for Mix<A, B, M> {
+    fn value(&self, u: Num, v: Num, p: Vec3) -> Vec3 {
+        let amount = self.amount.value(u, v, p);
+        (1.0 - amount) * self.a.value(u, v, p) + amount * self.b.value(u, v, p)
+    }
+}
@@ -0,0 +1,446 @@
+use crate::num::Num;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+use std::path::Path;
+
+/// How texture coordinates outside of the image are mapped back into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wrap {
+    /// Tile the image.
+    Repeat,
+    /// Extend the pixels at the borders.
+    Clamp,
+    /// Tile the image, flipping every other copy.
+    Mirror,
+}
+
+impl Wrap {
+    fn apply(self, i: isize, size: usize) -> usize {
+        let n = size as isize;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(n),
+            Wrap::Clamp => i.max(0).min(n - 1),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Clone)]
+struct Level {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Level {
+    fn pixel(&self, x: isize, y: isize, wrap: Wrap) -> Vec3 {
+        let x = wrap.apply(x, self.width);
+        let y = wrap.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+
+    fn lookup(&self, u: Num, v: Num, filter: Filter, wrap: Wrap) -> Vec3 {
+        // Rows are stored from the top of the image but `v` grows upwards.
+        let x = u * self.width as Num;
+        let y = (1.0 - v) * self.height as Num;
+        match filter {
+            Filter::Nearest => self.pixel(x.floor() as isize, y.floor() as isize, wrap),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (dx, dy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                (1.0 - dx) * (1.0 - dy) * self.pixel(x0, y0, wrap)
+                    + dx * (1.0 - dy) * self.pixel(x0 + 1, y0, wrap)
+                    + (1.0 - dx) * dy * self.pixel(x0, y0 + 1, wrap)
+                    + dx * dy * self.pixel(x0 + 1, y0 + 1, wrap)
+            }
+        }
+    }
+
+    /// A level with half the resolution, averaging each block of 2x2 pixels.
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (x, y) = (2 * x as isize, 2 * y as isize);
+                let sum = self.pixel(x, y, Wrap::Clamp)
+                    + self.pixel(x + 1, y, Wrap::Clamp)
+                    + self.pixel(x, y + 1, Wrap::Clamp)
+                    + self.pixel(x + 1, y + 1, Wrap::Clamp);
+                pixels.push(0.25 * sum);
+            }
+        }
+        Level {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// A texture looking up the colors of an image, stored row by row starting from the top.
+///
+/// By default the image is filtered bilinearly and repeated outside of the unit square.
+#[derive(Clone)]
+pub struct ImageTexture {
+    levels: Vec<Level>,
+    filter: Filter,
+    wrap: Wrap,
+    /// The width in texture coordinates of the region averaged by `value`.
+    footprint: Num,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert!(width > 0 && height > 0);
+        assert_eq!(pixels.len(), width * height);
+        ImageTexture {
+            levels: vec![Level {
+                width,
+                height,
+                pixels,
+            }],
+            filter: Filter::Bilinear,
+            wrap: Wrap::Repeat,
+            footprint: 0.0,
+        }
+    }
+
+    /// Reads a PPM, PNG or Radiance HDR image, chosen by the extension of `path`.
+    ///
+    /// The colors of PPM and PNG images are converted from the gamma 2 encoding used by `render`
+    /// to linear values, HDR images are already linear.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let file = BufReader::new(File::open(path)?);
+        let (width, height, pixels) = match extension.as_deref() {
+            Some("ppm") => read_ppm(file)?,
+            Some("png") => read_png(file)?,
+            Some("hdr") => read_hdr(file)?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "unsupported image format",
+                ))
+            }
+        };
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Precomputes downsampled copies of the image down to a single pixel, used to avoid aliasing
+    /// when the image is seen from far away. `value` averages the image over `footprint` units of
+    /// texture coordinates, roughly what a pixel of the render covers on the texture.
+    pub fn with_mipmaps(mut self, footprint: Num) -> Self {
+        self.footprint = footprint;
+        self.levels.truncate(1);
+        loop {
+            let last = &self.levels[self.levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            self.levels.push(next);
+        }
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Vec3 {
+        self.levels[0].pixels[y * self.width() + x]
+    }
+
+    /// The color averaged over a region `width` units of texture coordinates wide around `u`
+    /// and `v`, interpolating between the two closest mipmap levels. Without mipmaps this looks
+    /// up the full image.
+    pub fn filtered(&self, u: Num, v: Num, width: Num) -> Vec3 {
+        let last = (self.levels.len() - 1) as Num;
+        let texels = width * self.width().max(self.height()) as Num;
+        let level = if texels > 1.0 {
+            texels.log2().min(last)
+        } else {
+            0.0
+        };
+
+        let lower = level.floor();
+        let t = level - lower;
+        let lower = lower as usize;
+        let color = self.levels[lower].lookup(u, v, self.filter, self.wrap);
+        if t > 0.0 {
+            let upper = self.levels[lower + 1].lookup(u, v, self.filter, self.wrap);
+            (1.0 - t) * color + t * upper
+        } else {
+            color
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: Num, v: Num, _: Vec3) -> Vec3 {
+        self.filtered(u, v, self.footprint)
+    }
+}
+
+type Image = (usize, usize, Vec<Vec3>);
+
+/// The largest number of pixels read from an image file, 16384 by 16384.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// The number of pixels of an image read from a file, checked before allocating it since the size
+/// comes from the file.
+fn pixel_count(width: usize, height: usize) -> Result<usize> {
+    match width.checked_mul(height) {
+        Some(count) if count > 0 && count <= MAX_PIXELS => Ok(count),
+        _ => Err(invalid("invalid image size")),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Converts an 8 or 16 bit gamma encoded channel to a linear value.
+fn decode(value: u32, max: u32) -> Num {
+    let value = value as Num / max as Num;
+    value * value
+}
+
+/// Reads the next whitespace separated token of a PPM header, skipping comments.
+fn ppm_token(file: &mut impl BufRead) -> Result<String> {
+    let mut token = String::new();
+    let mut byte = [0];
+    loop {
+        file.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' => {
+                let mut comment = Vec::new();
+                file.read_until(b'\n', &mut comment)?;
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            c if c.is_ascii_whitespace() => {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            c => token.push(c as char),
+        }
+    }
+}
+
+fn ppm_number(file: &mut impl BufRead) -> Result<u32> {
+    ppm_token(file)?
+        .parse()
+        .map_err(|_| invalid("invalid number in PPM file"))
+}
+
+/// Reads an ASCII (P3) or binary (P6) PPM image.
+fn read_ppm(mut file: impl BufRead) -> Result<Image> {
+    let magic = ppm_token(&mut file)?;
+    let width = ppm_number(&mut file)? as usize;
+    let height = ppm_number(&mut file)? as usize;
+    let max = ppm_number(&mut file)?;
+    if max == 0 || max > 65535 {
+        return Err(invalid("invalid maximum value in PPM file"));
+    }
+
+    let count = 3 * pixel_count(width, height)?;
+    let mut values = Vec::with_capacity(count);
+    match magic.as_str() {
+        "P3" => {
+            for _ in 0..count {
+                values.push(ppm_number(&mut file)?);
+            }
+        }
+        "P6" => {
+            let size = if max < 256 { 1 } else { 2 };
+            let mut bytes = vec![0; count * size];
+            file.read_exact(&mut bytes)?;
+            values.extend(bytes.chunks(size).map(|chunk| match chunk {
+                [value] => u32::from(*value),
+                [hi, lo] => u32::from(*hi) << 8 | u32::from(*lo),
+                _ => unreachable!(),
+            }));
+        }
+        _ => return Err(invalid("unsupported PPM format")),
+    }
+
+    let pixels = values
+        .chunks(3)
+        .map(|c| Vec3::new(decode(c[0], max), decode(c[1], max), decode(c[2], max)))
+        .collect();
+    Ok((width, height, pixels))
+}
+
+fn read_png(file: impl Read) -> Result<Image> {
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let header = reader.info();
+    pixel_count(header.width as usize, header.height as usize)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+    let pixels = buffer[..info.buffer_size()]
+        .chunks(channels)
+        .map(|c| {
+            let channel = |i: usize| decode(u32::from(c[i]), 255);
+            if channels < 3 {
+                // Grayscale, possibly with alpha.
+                Vec3::from_scalar(channel(0))
+            } else {
+                Vec3::new(channel(0), channel(1), channel(2))
+            }
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+/// Reads a Radiance RGBE image, with or without run length encoded scanlines. Only the usual
+/// orientation, with the rows from top to bottom, is supported.
+fn read_hdr(mut file: impl BufRead) -> Result<Image> {
+    let mut line = String::new();
+    file.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid("missing HDR signature"));
+    }
+    loop {
+        line.clear();
+        if file.read_line(&mut line)? == 0 {
+            return Err(invalid("unexpected end of HDR header"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("unsupported HDR pixel format"));
+        }
+    }
+
+    line.clear();
+    file.read_line(&mut line)?;
+    let resolution = line.split_whitespace().collect::<Vec<_>>();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => (
+            height.parse().map_err(|_| invalid("invalid HDR height"))?,
+            width.parse().map_err(|_| invalid("invalid HDR width"))?,
+        ),
+        _ => return Err(invalid("unsupported HDR orientation")),
+    };
+
+    let mut pixels = Vec::with_capacity(pixel_count(width, height)?);
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_hdr_scanline(&mut file, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+            if e == 0 {
+                Vec3::default()
+            } else {
+                let scale = Num::powi(2.0, i32::from(e) - 136);
+                Vec3::new(
+                    Num::from(r) * scale,
+                    Num::from(g) * scale,
+                    Num::from(b) * scale,
+                )
+            }
+        }));
+    }
+    Ok((width, height, pixels))
+}
+
+fn read_hdr_scanline(file: &mut impl Read, scanline: &mut [[u8; 4]]) -> Result<()> {
+    let width = scanline.len();
+    let mut start = [0; 4];
+    file.read_exact(&mut start)?;
+
+    let encoded = (8..0x8000).contains(&width)
+        && start[0] == 2
+        && start[1] == 2
+        && start[2] & 0x80 == 0
+        && (usize::from(start[2]) << 8 | usize::from(start[3])) == width;
+
+    if !encoded {
+        scanline[0] = start;
+        for pixel in &mut scanline[1..] {
+            file.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+
+    // Each of the four components is stored separately as runs of equal bytes or literal
+    // bytes.
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut header = [0; 2];
+            file.read_exact(&mut header[..1])?;
+            if header[0] > 128 {
+                let count = usize::from(header[0] - 128);
+                if x + count > width {
+                    return Err(invalid("invalid HDR run length"));
+                }
+                file.read_exact(&mut header[1..])?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[component] = header[1];
+                }
+                x += count;
+            } else {
+                let count = usize::from(header[0]);
+                if count == 0 || x + count > width {
+                    return Err(invalid("invalid HDR run length"));
+                }
+                let mut bytes = vec![0; count];
+                file.read_exact(&mut bytes)?;
+                for (pixel, byte) in scanline[x..x + count].iter_mut().zip(bytes) {
+                    pixel[component] = byte;
+                }
+                x += count;
+            }
+        }
+    }
+    Ok(())
+}
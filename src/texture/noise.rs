@@ -0,0 +1,418 @@
+use crate::num::Num;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+use rand::prelude::*;
+
+/// A smooth pseudo-random function of the position, with values roughly between -1 and 1.
+pub trait Noise {
+    fn noise(&self, p: Vec3) -> Num;
+}
+
+/// A table of the numbers from 0 to 255 shuffled with a seeded generator, so the same seed
+/// always produces the same noise.
+#[derive(Clone)]
+struct Permutation([u8; 256]);
+
+impl Permutation {
+    fn new(seed: u64) -> Self {
+        let mut table = [0; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        table.shuffle(&mut StdRng::seed_from_u64(seed));
+        Permutation(table)
+    }
+
+    #[inline(always)]
+    fn get(&self, i: usize) -> usize {
+        self.0[i & 255] as usize
+    }
+}
+
+/// Splits `x` in its integer part, wrapped to the size of the permutation table, and its
+/// fractional part.
+#[inline(always)]
+fn split(x: Num) -> (usize, Num) {
+    let floor = x.floor();
+    ((floor as i64 & 255) as usize, x - floor)
+}
+
+/// The improved gradient noise of Ken Perlin.
+#[derive(Clone)]
+pub struct Perlin {
+    permutation: Permutation,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin {
+            permutation: Permutation::new(seed),
+        }
+    }
+}
+
+impl Noise for Perlin {
+    fn noise(&self, p: Vec3) -> Num {
+        let (xi, x) = split(p.x());
+        let (yi, y) = split(p.y());
+        let (zi, z) = split(p.z());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let perm = |i| self.permutation.get(i);
+        let a = perm(xi) + yi;
+        let aa = perm(a) + zi;
+        let ab = perm(a + 1) + zi;
+        let b = perm(xi + 1) + yi;
+        let ba = perm(b) + zi;
+        let bb = perm(b + 1) + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(perm(ab), x, y - 1.0, z),
+                    grad(perm(bb), x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(perm(aa + 1), x, y, z - 1.0),
+                    grad(perm(ba + 1), x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(perm(ab + 1), x, y - 1.0, z - 1.0),
+                    grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+fn fade(t: Num) -> Num {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: Num, a: Num, b: Num) -> Num {
+    a + t * (b - a)
+}
+
+/// The dot product of `(x, y, z)` with one of the twelve gradients pointing to the edges of a
+/// cube, chosen by `hash`.
+fn grad(hash: usize, x: Num, y: Num, z: Num) -> Num {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Simplex noise, which interpolates over a tetrahedral grid and is cheaper than `Perlin` while
+/// showing fewer axis aligned artifacts.
+#[derive(Clone)]
+pub struct Simplex {
+    permutation: Permutation,
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Simplex {
+            permutation: Permutation::new(seed),
+        }
+    }
+}
+
+impl Noise for Simplex {
+    fn noise(&self, p: Vec3) -> Num {
+        const F3: Num = 1.0 / 3.0;
+        const G3: Num = 1.0 / 6.0;
+
+        let (x, y, z) = (p.x(), p.y(), p.z());
+
+        // Skew the space to find the cell of the simplex grid containing the point.
+        let s = (x + y + z) * F3;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * G3;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+        let z0 = z - (k - t);
+
+        // Find which of the six tetrahedra of the cell contains the point.
+        let (i1, j1, k1, i2, j2, k2) = if x0 >= y0 {
+            if y0 >= z0 {
+                (1, 0, 0, 1, 1, 0)
+            } else if x0 >= z0 {
+                (1, 0, 0, 1, 0, 1)
+            } else {
+                (0, 0, 1, 1, 0, 1)
+            }
+        } else if y0 < z0 {
+            (0, 0, 1, 0, 1, 1)
+        } else if x0 < z0 {
+            (0, 1, 0, 0, 1, 1)
+        } else {
+            (0, 1, 0, 1, 1, 0)
+        };
+
+        let corners = [
+            (0, 0, 0, x0, y0, z0),
+            (
+                i1,
+                j1,
+                k1,
+                x0 - i1 as Num + G3,
+                y0 - j1 as Num + G3,
+                z0 - k1 as Num + G3,
+            ),
+            (
+                i2,
+                j2,
+                k2,
+                x0 - i2 as Num + 2.0 * G3,
+                y0 - j2 as Num + 2.0 * G3,
+                z0 - k2 as Num + 2.0 * G3,
+            ),
+            (
+                1,
+                1,
+                1,
+                x0 - 1.0 + 3.0 * G3,
+                y0 - 1.0 + 3.0 * G3,
+                z0 - 1.0 + 3.0 * G3,
+            ),
+        ];
+
+        let (ii, jj, kk) = (
+            (i as i64 & 255) as usize,
+            (j as i64 & 255) as usize,
+            (k as i64 & 255) as usize,
+        );
+        let perm = |i| self.permutation.get(i);
+
+        let mut n = 0.0;
+        for &(di, dj, dk, x, y, z) in &corners {
+            let t = 0.6 - x * x - y * y - z * z;
+            if t > 0.0 {
+                let hash = perm(ii + di + perm(jj + dj + perm(kk + dk)));
+                let t2 = t * t;
+                n += t2 * t2 * grad(hash % 12, x, y, z);
+            }
+        }
+        32.0 * n
+    }
+}
+
+/// Cellular noise, the distance from the point to the closest of a set of feature points
+/// scattered one per unit cube.
+#[derive(Clone)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Worley { seed }
+    }
+
+    /// The distance to the closest feature point.
+    pub fn distance(&self, p: Vec3) -> Num {
+        let (x, y, z) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let mut closest = Num::INFINITY;
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let cell = (x as i64 + dx, y as i64 + dy, z as i64 + dz);
+                    let h = hash(self.seed, cell);
+                    let feature = Vec3::new(
+                        cell.0 as Num + unit(h),
+                        cell.1 as Num + unit(h >> 21),
+                        cell.2 as Num + unit(h >> 42),
+                    );
+                    let d = p - feature;
+                    closest = closest.min(d.dot(d));
+                }
+            }
+        }
+        closest.sqrt()
+    }
+}
+
+impl Noise for Worley {
+    fn noise(&self, p: Vec3) -> Num {
+        2.0 * self.distance(p).min(1.0) - 1.0
+    }
+}
+
+/// Hashes the coordinates of a cell, mixing the bits with the finalizer of SplitMix64.
+fn hash(seed: u64, (x, y, z): (i64, i64, i64)) -> u64 {
+    let mut h = seed;
+    for &c in &[x, y, z] {
+        h = (h ^ c as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+    }
+    h
+}
+
+/// Maps the lowest 21 bits of `h` to a number between 0 and 1.
+fn unit(h: u64) -> Num {
+    (h & 0x1f_ffff) as Num / 0x20_0000 as Num
+}
+
+/// Fractional Brownian motion, a sum of `octaves` layers of noise each with twice the frequency
+/// and half the amplitude of the previous one. The result is normalized to the range of `noise`.
+pub fn fbm<N: Noise>(noise: &N, p: Vec3, octaves: usize) -> Num {
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut p = p;
+    for _ in 0..octaves {
+        sum += amplitude * noise.noise(p);
+        total += amplitude;
+        amplitude *= 0.5;
+        p *= 2.0;
+    }
+    if total > 0.0 {
+        sum / total
+    } else {
+        0.0
+    }
+}
+
+/// Like `fbm` but adding the absolute value of each layer, which gives creases where the noise
+/// crosses zero. The result is between 0 and 1.
+pub fn turbulence<N: Noise>(noise: &N, p: Vec3, octaves: usize) -> Num {
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut p = p;
+    for _ in 0..octaves {
+        sum += amplitude * noise.noise(p).abs();
+        total += amplitude;
+        amplitude *= 0.5;
+        p *= 2.0;
+    }
+    if total > 0.0 {
+        sum / total
+    } else {
+        0.0
+    }
+}
+
+/// A grey texture with the `fbm` of the noise at the hit position.
+#[derive(Clone)]
+pub struct Fbm<N: Noise> {
+    noise: N,
+    frequency: Num,
+    octaves: usize,
+}
+
+impl<N: Noise> Fbm<N> {
+    pub fn new(noise: N, frequency: Num, octaves: usize) -> Self {
+        Fbm {
+            noise,
+            frequency,
+            octaves,
+        }
+    }
+}
+
+impl<N: Noise> Texture for Fbm<N> {
+    fn value(&self, _: Num, _: Num, p: Vec3) -> Vec3 {
+        let n = fbm(&self.noise, self.frequency * p, self.octaves);
+        Vec3::from_scalar(0.5 * (1.0 + n))
+    }
+}
+
+/// A grey texture with the `turbulence` of the noise at the hit position.
+#[derive(Clone)]
+pub struct Turbulence<N: Noise> {
+    noise: N,
+    frequency: Num,
+    octaves: usize,
+}
+
+impl<N: Noise> Turbulence<N> {
+    pub fn new(noise: N, frequency: Num, octaves: usize) -> Self {
+        Turbulence {
+            noise,
+            frequency,
+            octaves,
+        }
+    }
+}
+
+impl<N: Noise> Texture for Turbulence<N> {
+    fn value(&self, _: Num, _: Num, p: Vec3) -> Vec3 {
+        Vec3::from_scalar(turbulence(&self.noise, self.frequency * p, self.octaves))
+    }
+}
+
+/// Veins of `dark` over `light` along the z axis, perturbed by turbulence.
+#[derive(Clone)]
+pub struct Marble<N: Noise> {
+    noise: N,
+    frequency: Num,
+    dark: Vec3,
+    light: Vec3,
+}
+
+impl<N: Noise> Marble<N> {
+    pub fn new(noise: N, frequency: Num, dark: Vec3, light: Vec3) -> Self {
+        Marble {
+            noise,
+            frequency,
+            dark,
+            light,
+        }
+    }
+}
+
+impl<N: Noise> Texture for Marble<N> {
+    fn value(&self, _: Num, _: Num, p: Vec3) -> Vec3 {
+        let p = self.frequency * p;
+        let t = 0.5 * (1.0 + (p.z() + 10.0 * turbulence(&self.noise, p, 7)).sin());
+        t * self.light + (1.0 - t) * self.dark
+    }
+}
+
+/// Rings of `dark` and `light` around the y axis, distorted by noise.
+#[derive(Clone)]
+pub struct Wood<N: Noise> {
+    noise: N,
+    frequency: Num,
+    dark: Vec3,
+    light: Vec3,
+}
+
+impl<N: Noise> Wood<N> {
+    pub fn new(noise: N, frequency: Num, dark: Vec3, light: Vec3) -> Self {
+        Wood {
+            noise,
+            frequency,
+            dark,
+            light,
+        }
+    }
+}
+
+impl<N: Noise> Texture for Wood<N> {
+    fn value(&self, _: Num, _: Num, p: Vec3) -> Vec3 {
+        let p = self.frequency * p;
+        let radius = (p.x() * p.x() + p.z() * p.z()).sqrt() + 0.5 * fbm(&self.noise, p, 4);
+        let t = radius - radius.floor();
+        t * self.light + (1.0 - t) * self.dark
+    }
+}
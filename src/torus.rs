@@ -0,0 +1,138 @@
+use crate::bounding_box::BoundingBox;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::num::*;
+use crate::poly::solve_quartic;
+use crate::quadric::Frame;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+/// A torus around `axis`, made of the points at distance `minor` from the circle of radius
+/// `major` centered at `center`. Its texture coordinates are the angles around the axis and
+/// around the tube, going from 0 to 1.
+pub struct Torus<T: Material> {
+    frame: Frame,
+    major: Num,
+    minor: Num,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for Torus<T> {}
+
+impl<T: Material> Torus<T> {
+    pub fn new(center: Vec3, axis: Vec3, major: Num, minor: Num, material: T) -> Self {
+        Torus {
+            frame: Frame::new(center, axis),
+            major,
+            minor,
+            material,
+        }
+    }
+
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<Num> {
+        // Solving in units of the major radius, along a unit direction and from the point of the
+        // ray closest to the center keeps the coefficients of the quartic small.
+        let (o, d, _) = self.frame.ray_to_local(r);
+        let len = d.len();
+        let (o, d) = (o / self.major, d / len);
+        let minor = self.minor / self.major;
+        let shift = -o.dot(d);
+        let c = o + shift * d;
+
+        // `(|p|² + 1 - minor²)² = 4 * (x² + y²)` along the ray.
+        let k = c.dot(d);
+        let m = c.dot(c) + 1.0 - minor * minor;
+        let dxy = d.x() * d.x() + d.y() * d.y();
+        let roots = solve_quartic(
+            4.0 * k,
+            4.0 * k * k + 2.0 * m - 4.0 * dxy,
+            4.0 * k * m - 8.0 * (c.x() * d.x() + c.y() * d.y()),
+            m * m - 4.0 * (c.x() * c.x() + c.y() * c.y()),
+        );
+
+        // The roots are less accurate than the other intersections, so origins close enough to
+        // the surface for them to be unreliable are taken as on it, and their closest root as the
+        // origin itself.
+        let oxy = o.x() * o.x() + o.y() * o.y();
+        let magnitude = o.dot(o) + 1.0 + minor * minor;
+        let f = (o.dot(o) + 1.0 - minor * minor).powi(2) - 4.0 * oxy;
+        let origin = if f.abs() <= 1024.0 * Num::EPSILON * (magnitude + 2.0).powi(2) {
+            roots
+                .iter()
+                .cloned()
+                .min_by(|a, b| (a + shift).abs().partial_cmp(&(b + shift).abs()).unwrap())
+        } else {
+            None
+        };
+
+        let scale = self.major / len;
+        roots
+            .iter()
+            .filter(|&&s| Some(s) != origin)
+            .map(|&s| (s + shift) * scale)
+            .find(|&t| t > t_min && t < t_max)
+    }
+}
+
+impl<T: Material> Hitable for Torus<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        let t = match self.intersect(r, t_min, t_max) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        // Projecting the hit point back on the torus bounds its error independently of the ray.
+        let (o, d, _) = self.frame.ray_to_local(r);
+        let hit = o + t * d;
+        let distance = (hit.x() * hit.x() + hit.y() * hit.y()).sqrt();
+        let radial = if distance > 0.0 {
+            Vec3::new(hit.x() / distance, hit.y() / distance, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let ring = self.major * radial;
+        let tube = hit - ring;
+        let tube_len = tube.len();
+        let normal = if tube_len > 0.0 {
+            tube / tube_len
+        } else {
+            radial
+        };
+        let local = ring + self.minor * normal;
+        let local_error = gamma(8) * (ring.abs() + self.minor * normal.abs());
+        let (p, p_error) = self.frame.point_to_world(local, local_error);
+
+        let phi = radial.y().atan2(radial.x());
+        let theta = normal.z().atan2(normal.dot(radial));
+        let world_normal = self.frame.vector_to_world(normal);
+
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = p_error;
+        rec.set_normals(r, world_normal, world_normal);
+        rec.u = (phi + PI) / (2.0 * PI);
+        rec.v = (theta + PI) / (2.0 * PI);
+        rec.dpdu = self
+            .frame
+            .vector_to_world(2.0 * PI * Vec3::new(-local.y(), local.x(), 0.0));
+        rec.dpdv = self.frame.vector_to_world(
+            2.0 * PI * self.minor * (theta.cos() * Vec3::new(0.0, 0.0, 1.0) - theta.sin() * radial),
+        );
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+        true
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        let outer = self.major + self.minor;
+        Some(self.frame.bounding_box(
+            Vec3::new(-outer, -outer, -self.minor),
+            Vec3::new(outer, outer, self.minor),
+        ))
+    }
+}
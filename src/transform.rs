@@ -0,0 +1,299 @@
+use crate::bounding_box::BoundingBox;
+use crate::matrix::{Mat3, Mat4};
+use crate::num::*;
+use crate::quaternion::Quaternion;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::ops::Mul;
+
+/// An affine transformation stored as a 4x4 matrix together with its inverse.
+#[derive(Clone, Debug)]
+pub struct Transform {
+    m: Mat4,
+    m_inv: Mat4,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            m: Mat4::identity(),
+            m_inv: Mat4::identity(),
+        }
+    }
+
+    pub fn translate(delta: Vec3) -> Self {
+        Transform {
+            m: Mat4::from_linear(&Mat3::identity(), delta),
+            m_inv: Mat4::from_linear(&Mat3::identity(), -delta),
+        }
+    }
+
+    pub fn scale(factor: Vec3) -> Self {
+        Transform {
+            m: Mat4::from_linear(&Mat3::diagonal(factor), Vec3::default()),
+            m_inv: Mat4::from_linear(&Mat3::diagonal(1.0 / factor), Vec3::default()),
+        }
+    }
+
+    /// A rotation of `angle` degrees around `axis`, counterclockwise when looking down the axis.
+    pub fn rotate(axis: Vec3, angle: Num) -> Self {
+        let rotation = Quaternion::from_axis_angle(axis, angle).to_mat3();
+        Transform {
+            m: Mat4::from_linear(&rotation, Vec3::default()),
+            m_inv: Mat4::from_linear(&rotation.transpose(), Vec3::default()),
+        }
+    }
+
+    /// Places an object at `eye` facing `target`. In object space the object faces the -z axis
+    /// with +y pointing up, like the `Camera` does.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let w = (eye - target).unit();
+        let u = up.cross(w).unit();
+        let v = w.cross(u);
+
+        let rotation = Mat3::from_cols(u, v, w);
+        let inverse = rotation.transpose();
+        Transform {
+            m: Mat4::from_linear(&rotation, eye),
+            m_inv: Mat4::from_linear(&inverse, -(inverse * eye)),
+        }
+    }
+
+    /// The transform with matrix `m`, or `None` if it can't be inverted.
+    pub fn from_matrix(m: Mat4) -> Option<Self> {
+        let m_inv = m.inverse()?;
+        Some(Transform { m, m_inv })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.m
+    }
+
+    pub fn inverse(&self) -> Self {
+        Transform {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.m.point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.m.vector(v)
+    }
+
+    /// Transforms the point `p` whose coordinates have absolute errors bounded by `p_error`,
+    /// returning the new point and a bound on its errors, which includes the rounding errors of
+    /// the transform.
+    pub fn point_with_error(&self, p: Vec3, p_error: Vec3) -> (Vec3, Vec3) {
+        let linear = self.m.linear().abs();
+        let error = (gamma(3) + 1.0) * (linear * p_error)
+            + gamma(3) * (linear * p.abs() + self.m.translation().abs());
+        (self.point(p), error)
+    }
+
+    /// Transforms the origin and direction of `r`, the direction is not normalized.
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::with_time(self.point(r.origin()), self.vector(r.direction()), r.time())
+    }
+
+    /// Normals transform with the inverse transpose, the result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.m_inv.transpose_vector(n)
+    }
+
+    pub fn bounding_box(&self, bounding_box: &BoundingBox) -> BoundingBox {
+        let lo = bounding_box.min();
+        let hi = bounding_box.max();
+        let mut small = Vec3::from_scalar(MAX_NUM);
+        let mut big = Vec3::from_scalar(-MAX_NUM);
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { lo.x() } else { hi.x() },
+                if i & 2 == 0 { lo.y() } else { hi.y() },
+                if i & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            let p = self.point(corner);
+            small = small.min(&p);
+            big = big.max(&p);
+        }
+        BoundingBox::new(small, big)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Composition, `a * b` applies `b` first and then `a`.
+impl<'a> Mul<&'a Transform> for &'a Transform {
+    type Output = Transform;
+
+    fn mul(self, other: &'a Transform) -> Self::Output {
+        Transform {
+            m: self.m * other.m,
+            m_inv: other.m_inv * self.m_inv,
+        }
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, other: Transform) -> Self::Output {
+        &self * &other
+    }
+}
+
+/// A transform moving from `start` at `time0` to `end` at `time1`.
+///
+/// Each transform is split in a translation, a rotation and a scale, which are interpolated
+/// separately so rotating objects keep their shape while they move.
+#[derive(Clone, Debug)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    time0: Num,
+    time1: Num,
+    parts: [(Vec3, Quaternion, Mat3); 2],
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, time0: Num, end: Transform, time1: Num) -> Self {
+        let parts = [decompose(&start.m), decompose(&end.m)];
+        AnimatedTransform {
+            start,
+            end,
+            time0,
+            time1,
+            parts,
+        }
+    }
+
+    pub fn start(&self) -> &Transform {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Transform {
+        &self.end
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.time1 > self.time0 && self.start.m != self.end.m
+    }
+
+    /// The transform at `time`, clamped to the time range of the animation.
+    pub fn at(&self, time: Num) -> Transform {
+        if !self.is_animated() || time <= self.time0 {
+            return self.start.clone();
+        }
+        if time >= self.time1 {
+            return self.end.clone();
+        }
+
+        let t = (time - self.time0) / (self.time1 - self.time0);
+        let [(t0, r0, s0), (t1, r1, s1)] = &self.parts;
+
+        let translation = t0.lerp(*t1, t);
+        let rotation = r0.slerp(*r1, t).to_mat3();
+        let scale = *s0 + (*s1 - *s0) * t;
+
+        Transform::from_matrix(Mat4::from_linear(&(rotation * scale), translation))
+            .unwrap_or_else(|| self.start.clone())
+    }
+
+    /// A box containing `bounding_box` transformed at every time between `time0` and `time1`.
+    pub fn bounding_box(&self, bounding_box: &BoundingBox, time0: Num, time1: Num) -> BoundingBox {
+        const STEPS: usize = 32;
+        let result = self
+            .at(time0)
+            .bounding_box(bounding_box)
+            .surrounding_box(&self.at(time1).bounding_box(bounding_box));
+        // The transform only changes inside the time range of the animation.
+        let (start, end) = (max(time0, self.time0), min(time1, self.time1));
+        if !self.is_animated() || start >= end {
+            return result;
+        }
+
+        // Between two samples the corners of the box stray from the segment joining their
+        // positions by at most an eighth of the squared step times their acceleration, which
+        // comes from the rotation and from the scale changing while it rotates.
+        let [(_, r0, s0), (_, r1, s1)] = &self.parts;
+        let (speed, acceleration) = rotation_bounds(*r0, *r1);
+        let step = (end - start) / (self.time1 - self.time0) / STEPS as Num;
+        let (lo, hi) = (bounding_box.min(), bounding_box.max());
+        let mut pad: Num = 0.0;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { lo.x() } else { hi.x() },
+                if i & 2 == 0 { lo.y() } else { hi.y() },
+                if i & 4 == 0 { lo.z() } else { hi.z() },
+            );
+            let (a, b) = (*s0 * corner, *s1 * corner);
+            let radius = max(a.len(), b.len());
+            let deviation = acceleration * radius + 2.0 * speed * (b - a).len();
+            pad = max(pad, step * step / 8.0 * deviation);
+        }
+
+        let pad = Vec3::from_scalar(pad);
+        (0..=STEPS).fold(result, |result, i| {
+            let time = start + (end - start) * i as Num / STEPS as Num;
+            let sample = self.at(time).bounding_box(bounding_box);
+            let sample = BoundingBox::new(sample.min() - pad, sample.max() + pad);
+            result.surrounding_box(&sample)
+        })
+    }
+}
+
+/// Bounds on the angular speed and acceleration of `r0.slerp(r1, t)` as `t` goes from 0 to 1. It
+/// turns at twice the angle between the quaternions, except for close rotations where it falls
+/// back to a normalized lerp, which turns slightly faster in the middle.
+fn rotation_bounds(r0: Quaternion, r1: Quaternion) -> (Num, Num) {
+    let cos = min(r0.dot(r1).abs(), 1.0);
+    let theta = cos.acos();
+    if cos > 0.9995 {
+        let speed = 2.0 * theta / cos;
+        (
+            speed,
+            speed * speed + 2.0 * theta * theta * theta / (cos * cos),
+        )
+    } else {
+        let speed = 2.0 * theta;
+        (speed, speed * speed)
+    }
+}
+
+/// Splits the affine transform `m` in a translation, a rotation and a scale, which may include
+/// shear, using the polar decomposition of its linear part.
+fn decompose(m: &Mat4) -> (Vec3, Quaternion, Mat3) {
+    let linear = m.linear();
+
+    // Averaging a matrix with its inverse transpose converges to its closest rotation.
+    let mut rotation = linear;
+    for _ in 0..100 {
+        let inverse = match rotation.inverse() {
+            Some(inverse) => inverse,
+            None => break,
+        };
+        let next = (rotation + inverse.transpose()) * 0.5;
+        let norm = (next - rotation).max_norm();
+        rotation = next;
+        // The convergence is quadratic, so the next step would be down to the rounding error.
+        if norm < Num::EPSILON.sqrt() {
+            break;
+        }
+    }
+
+    // Keep reflections in the scale so the rotation can be written as a quaternion.
+    if rotation.determinant() < 0.0 {
+        rotation = rotation * -1.0;
+    }
+
+    let scale = rotation.transpose() * linear;
+    (m.translation(), Quaternion::from_mat3(&rotation), scale)
+}
@@ -0,0 +1,98 @@
+use crate::bounding_box::BoundingBox;
+use crate::csg::{Solid, Span};
+use crate::hitable::*;
+use crate::num::Num;
+use crate::ray::Ray;
+use crate::transform::Transform;
+
+/// A hitable placed in the world by a transform.
+///
+/// Rays are moved to the object space of the hitable and its hits are moved back to world
+/// space. Unlike an `Instance`, the hitable is owned, so this is the way to rotate or scale a
+/// single object.
+pub struct Transformed<H: Hitable> {
+    object: H,
+    transform: Transform,
+    inverse: Transform,
+}
+
+impl<H: Hitable> Transformed<H> {
+    pub fn new(object: H, transform: Transform) -> Self {
+        let inverse = transform.inverse();
+        Transformed {
+            object,
+            transform,
+            inverse,
+        }
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+/// Moves a hit record computed in the object space of `transform` to world space.
+pub(crate) fn to_world(transform: &Transform, rec: &mut HitRecord) {
+    let (p, p_error) = transform.point_with_error(rec.p, rec.p_error);
+    rec.p = p;
+    rec.p_error = p_error;
+    rec.normal = transform.normal(rec.normal).unit();
+    rec.geometric_normal = transform.normal(rec.geometric_normal).unit();
+    rec.dpdu = transform.vector(rec.dpdu);
+    rec.dpdv = transform.vector(rec.dpdv);
+}
+
+impl<H: Hitable> Hitable for Transformed<H> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        if self.object.hit(&self.inverse.ray(r), t_min, t_max, rec) {
+            to_world(&self.transform, rec);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.object.occluded(&self.inverse.ray(r), t_min, t_max)
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        if self
+            .object
+            .hit_counting(&self.inverse.ray(r), t_min, t_max, rec, stats)
+        {
+            to_world(&self.transform, rec);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        let bounding_box = self.object.bounding_box(t0, t1)?;
+        Some(self.transform.bounding_box(&bounding_box))
+    }
+}
+
+impl<H: Solid> Solid for Transformed<H> {
+    fn spans<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, spans: &mut Vec<Span<'a>>) {
+        let start = spans.len();
+        self.object.spans(&self.inverse.ray(r), t_min, t_max, spans);
+        for span in &mut spans[start..] {
+            for rec in span.enter.iter_mut().chain(span.exit.iter_mut()) {
+                to_world(&self.transform, rec);
+            }
+        }
+    }
+}
@@ -1,15 +1,35 @@
 use crate::bounding_box::BoundingBox;
-use crate::hitable::{HitRecord, Hitable};
+use crate::hitable::{HitRecord, Hitable, Sampleable};
 use crate::material::Material;
 use crate::num::*;
+use crate::packet::{RayPacket, PACKET_SIZE};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
+/// A bound on the rounding error of the distance computed by the watertight test for the sheared
+/// triangle with vertices `$a`, `$b` and `$c` and edge functions `$e`, from "Physically Based
+/// Rendering". It's a macro so it works both on numbers and on packets.
+macro_rules! t_error {
+    ($a:expr, $b:expr, $c:expr, $e:expr, $inv_det:expr) => {{
+        let max_x = $a.0.abs().max($b.0.abs()).max($c.0.abs());
+        let max_y = $a.1.abs().max($b.1.abs()).max($c.1.abs());
+        let max_z = $a.2.abs().max($b.2.abs()).max($c.2.abs());
+        let max_e = $e.0.abs().max($e.1.abs()).max($e.2.abs());
+        let delta_x = gamma(5) * (max_x + max_z);
+        let delta_y = gamma(5) * (max_y + max_z);
+        let delta_z = gamma(3) * max_z;
+        let delta_e = 2.0 * (gamma(2) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+        3.0 * (gamma(3) * max_e * max_z + delta_e * max_z + delta_z * max_e) * $inv_det.abs()
+    }};
+}
+
 pub struct Triangle<T: Material> {
     normal: Vec3,
     p1: Vec3,
     p2: Vec3,
     p3: Vec3,
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[[Num; 2]; 3]>,
     material: T,
 }
 
@@ -22,6 +42,8 @@ impl<T: Material> Triangle<T> {
             p1,
             p2,
             p3,
+            normals: None,
+            uvs: None,
             material,
         }
     }
@@ -32,53 +54,367 @@ impl<T: Material> Triangle<T> {
             p1,
             p2,
             p3,
+            normals: None,
+            uvs: None,
             material,
         }
     }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn vertices(&self) -> [Vec3; 3] {
+        [self.p1, self.p2, self.p3]
+    }
+
+    pub fn vertex_normals(&self) -> Option<[Vec3; 3]> {
+        self.normals
+    }
+
+    pub fn uvs(&self) -> Option<[[Num; 2]; 3]> {
+        self.uvs
+    }
+
+    /// Sets the normals at each vertex, which are interpolated across the triangle to shade it
+    /// smoothly.
+    pub fn with_vertex_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Sets the texture coordinates at each vertex. Without them, the texture coordinates of a
+    /// hit are its barycentric coordinates.
+    pub fn with_uvs(mut self, uvs: [[Num; 2]; 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+/// A ray transformed so it points along the +z axis, used by the watertight ray/triangle test
+/// of Woop, Benthin and Wald. Transforming the ray once and reusing it for every triangle makes
+/// testing many triangles against the same ray cheaper.
+pub(crate) struct ShearedRay {
+    origin: Vec3,
+    kx: usize,
+    ky: usize,
+    kz: usize,
+    sx: Num,
+    sy: Num,
+    sz: Num,
+}
+
+impl ShearedRay {
+    pub fn new(r: &Ray) -> Self {
+        let (kx, ky, kz) = permutation(r.direction());
+        let d = r.direction();
+        ShearedRay {
+            origin: r.origin(),
+            kx,
+            ky,
+            kz,
+            sx: d.get(kx) / d.get(kz),
+            sy: d.get(ky) / d.get(kz),
+            sz: 1.0 / d.get(kz),
+        }
+    }
+
+    /// Intersects the ray with the triangle with vertices `p1`, `p2` and `p3`. Returns the
+    /// distance along the ray to the hit and the barycentric coordinates of the hit point with
+    /// respect to `p2` and `p3`. Rays hitting an edge shared by two triangles always hit one of
+    /// them.
+    pub fn intersect(
+        &self,
+        p1: Vec3,
+        p2: Vec3,
+        p3: Vec3,
+        t_min: Num,
+        t_max: Num,
+    ) -> Option<(Num, Num, Num)> {
+        let a = p1 - self.origin;
+        let b = p2 - self.origin;
+        let c = p3 - self.origin;
+
+        let ax = a.get(self.kx) - self.sx * a.get(self.kz);
+        let ay = a.get(self.ky) - self.sy * a.get(self.kz);
+        let bx = b.get(self.kx) - self.sx * b.get(self.kz);
+        let by = b.get(self.ky) - self.sy * b.get(self.kz);
+        let cx = c.get(self.kx) - self.sx * c.get(self.kz);
+        let cy = c.get(self.ky) - self.sy * c.get(self.kz);
+
+        let (u, v, w) = edge_functions((ax, ay), (bx, by), (cx, cy));
+
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0.0 {
+            return None;
+        }
+
+        let az = self.sz * a.get(self.kz);
+        let bz = self.sz * b.get(self.kz);
+        let cz = self.sz * c.get(self.kz);
+
+        let inv_det = 1.0 / det;
+        let t = (u * az + v * bz + w * cz) * inv_det;
+
+        if t > t_min
+            && t < t_max
+            && t > t_error!((ax, ay, az), (bx, by, bz), (cx, cy, cz), (u, v, w), inv_det)
+        {
+            Some((t, v * inv_det, w * inv_det))
+        } else {
+            None
+        }
+    }
+}
+
+/// The edge functions of the sheared triangle with vertices `a`, `b` and `c`, which are the
+/// unnormalized barycentric coordinates of the hit point. Single precision can't tell on which
+/// side of an edge a ray close to it passes, so those cases are recomputed in double precision.
+fn edge_functions(a: (Num, Num), b: (Num, Num), c: (Num, Num)) -> (Num, Num, Num) {
+    let u = c.0 * b.1 - c.1 * b.0;
+    let v = a.0 * c.1 - a.1 * c.0;
+    let w = b.0 * a.1 - b.1 * a.0;
+
+    #[cfg(feature = "f32")]
+    {
+        if u == 0.0 || v == 0.0 || w == 0.0 {
+            let det = |x: Num, y: Num, z: Num, w: Num| {
+                (f64::from(x) * f64::from(y) - f64::from(z) * f64::from(w)) as Num
+            };
+            return (
+                det(c.0, b.1, c.1, b.0),
+                det(a.0, c.1, a.1, c.0),
+                det(b.0, a.1, b.1, a.0),
+            );
+        }
+    }
+
+    (u, v, w)
+}
+
+/// The axes of the ray direction, sorted so the last one is the largest one in magnitude and the
+/// winding of the triangles is preserved.
+fn permutation(d: Vec3) -> (usize, usize, usize) {
+    let kz = d.max_axis();
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    if d.get(kz) < 0.0 {
+        (ky, kx, kz)
+    } else {
+        (kx, ky, kz)
+    }
+}
+
+/// The unit normal of the triangle with vertices `p1`, `p2` and `p3`, flipped if needed to point
+/// to the same side as the shading normal `normal`.
+pub(crate) fn geometric_normal(p1: Vec3, p2: Vec3, p3: Vec3, normal: Vec3) -> Vec3 {
+    let n = (p2 - p1).cross(p3 - p1).unit();
+    if n.dot(normal) < 0.0 {
+        -n
+    } else {
+        n
+    }
+}
+
+/// The texture coordinates at the point with barycentric coordinates `u` and `v` of the
+/// triangle with vertices `p`, and the derivatives of the position with respect to them.
+pub(crate) fn texture_coordinates(
+    uvs: Option<&[[Num; 2]; 3]>,
+    p: [Vec3; 3],
+    u: Num,
+    v: Num,
+) -> (Num, Num, Vec3, Vec3) {
+    let barycentric = (u, v, p[1] - p[0], p[2] - p[0]);
+    let uvs = match uvs {
+        Some(uvs) => uvs,
+        None => return barycentric,
+    };
+
+    let w = 1.0 - u - v;
+    let tu = w * uvs[0][0] + u * uvs[1][0] + v * uvs[2][0];
+    let tv = w * uvs[0][1] + u * uvs[1][1] + v * uvs[2][1];
+
+    let duv02 = [uvs[0][0] - uvs[2][0], uvs[0][1] - uvs[2][1]];
+    let duv12 = [uvs[1][0] - uvs[2][0], uvs[1][1] - uvs[2][1]];
+    let dp02 = p[0] - p[2];
+    let dp12 = p[1] - p[2];
+    let det = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+    if det.abs() < 1e-12 {
+        // The texture coordinates are degenerate, fall back to the barycentric derivatives.
+        return (tu, tv, barycentric.2, barycentric.3);
+    }
+    let inv_det = 1.0 / det;
+    let dpdu = (duv12[1] * dp02 - duv02[1] * dp12) * inv_det;
+    let dpdv = (duv02[0] * dp12 - duv12[0] * dp02) * inv_det;
+    (tu, tv, dpdu, dpdv)
+}
+
+/// The point with barycentric coordinates `u` and `v` with respect to the second and third
+/// vertices of `p`, and a bound on its rounding errors. Unlike the point along the ray, its error
+/// doesn't grow with the distance traveled by the ray.
+pub(crate) fn barycentric_point(p: [Vec3; 3], u: Num, v: Num) -> (Vec3, Vec3) {
+    let (a, b, c) = ((1.0 - u - v) * p[0], u * p[1], v * p[2]);
+    (a + b + c, gamma(7) * (a.abs() + b.abs() + c.abs()))
+}
+
+/// Interpolates the vertex normals at the point with barycentric coordinates `u` and `v`.
+pub(crate) fn interpolate(normals: &[Vec3; 3], u: Num, v: Num) -> Vec3 {
+    ((1.0 - u - v) * normals[0] + u * normals[1] + v * normals[2]).unit()
+}
+
+impl<T: Material> Triangle<T> {
+    fn intersect(&self, r: &Ray, t_min: Num, t_max: Num) -> Option<(Num, Num, Num)> {
+        ShearedRay::new(r).intersect(self.p1, self.p2, self.p3, t_min, t_max)
+    }
+
+    /// The shading normal at the point with barycentric coordinates `u` and `v`.
+    fn shading_normal(&self, u: Num, v: Num) -> Vec3 {
+        match &self.normals {
+            Some(normals) => interpolate(normals, u, v),
+            None => self.normal,
+        }
+    }
+
+    /// Fills `rec` for a hit of `r` at `t` with barycentric coordinates `u` and `v`.
+    fn record<'a>(&'a self, r: &Ray, t: Num, u: Num, v: Num, rec: &mut HitRecord<'a>) {
+        let normal = self.shading_normal(u, v);
+        let (p, p_error) = barycentric_point([self.p1, self.p2, self.p3], u, v);
+        rec.t = t;
+        rec.p = p;
+        rec.p_error = p_error;
+        rec.set_normals(
+            r,
+            geometric_normal(self.p1, self.p2, self.p3, normal),
+            normal,
+        );
+        let (u, v, dpdu, dpdv) =
+            texture_coordinates(self.uvs.as_ref(), [self.p1, self.p2, self.p3], u, v);
+        rec.u = u;
+        rec.v = v;
+        rec.dpdu = dpdu;
+        rec.dpdv = dpdv;
+        rec.primitive_id = 0;
+        rec.instance_id = None;
+        rec.material = &self.material;
+    }
 }
 
 impl<T: Material> Hitable for Triangle<T> {
     fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
-        let a = self.normal.dot(r.direction());
+        match self.intersect(r, t_min, t_max) {
+            Some((temp, u, v)) => {
+                self.record(r, temp, u, v, rec);
+                true
+            }
+            None => false,
+        }
+    }
 
-        if a != 0.0 {
-            let b = self.normal.dot(r.origin());
-            let c = self.normal.dot(self.p1);
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
 
-            let temp = (c - b) / a;
-            let p = r.point_at(temp);
+    fn hit_packet<'a>(
+        &'a self,
+        packet: &RayPacket,
+        t_min: Num,
+        t_max: &mut Numx4,
+        active: Maskx4,
+        recs: &mut [HitRecord<'a>; PACKET_SIZE],
+    ) -> Maskx4 {
+        let (kx, ky, kz) = permutation(packet.ray(0).direction());
+        if (1..PACKET_SIZE).any(|lane| permutation(packet.ray(lane).direction()) != (kx, ky, kz)) {
+            // The directions are too different to share the same shear, fall back to testing
+            // each ray on its own.
+            let mut hit = Maskx4::splat(false);
+            for (lane, rec) in recs.iter_mut().enumerate() {
+                if active.extract(lane)
+                    && self.hit(packet.ray(lane), t_min, t_max.extract(lane), rec)
+                {
+                    hit = hit.replace(lane, true);
+                    *t_max = t_max.replace(lane, rec.t);
+                }
+            }
+            return hit;
+        }
 
-            if temp < t_max && temp > t_min {
-                let u = self.p2 - self.p1;
-                let v = self.p3 - self.p1;
-                let w = p - self.p1;
-                let n = u.cross(v);
-                let norm = n.dot(n);
+        let origin = packet.origin();
+        let direction = packet.direction();
 
-                let gamma = u.cross(w).dot(n) / norm;
-                if gamma < 0.0 || gamma > 1.0 {
-                    return false;
-                }
+        let sz = 1.0 / direction[kz];
+        let sx = direction[kx] * sz;
+        let sy = direction[ky] * sz;
 
-                let beta = w.cross(v).dot(n) / norm;
-                if beta < 0.0 || beta > 1.0 {
-                    return false;
-                }
+        let shear = |p: Vec3| {
+            let (px, py, pz) = (
+                p.get(kx) - origin[kx],
+                p.get(ky) - origin[ky],
+                p.get(kz) - origin[kz],
+            );
+            (px - sx * pz, py - sy * pz, sz * pz)
+        };
 
-                let alpha = 1.0 - gamma - beta;
-                if alpha < 0.0 || alpha > 1.0 {
-                    return false;
-                }
+        let (ax, ay, az) = shear(self.p1);
+        let (bx, by, bz) = shear(self.p2);
+        let (cx, cy, cz) = shear(self.p3);
+
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+
+        let zero = Numx4::splat(0.0);
+        let negative = u.lt(zero) | v.lt(zero) | w.lt(zero);
+        let positive = u.gt(zero) | v.gt(zero) | w.gt(zero);
+
+        let det = u + v + w;
+        let inv_det = 1.0 / det;
+        let temp = (u * az + v * bz + w * cz) * inv_det;
+
+        // Lanes close to an edge need the double precision fallback of the scalar test.
+        let edge = if cfg!(feature = "f32") {
+            active & (u.eq(zero) | v.eq(zero) | w.eq(zero))
+        } else {
+            Maskx4::splat(false)
+        };
 
-                rec.t = temp;
-                rec.p = p;
-                rec.normal = self.normal;
-                rec.material = &self.material;
+        let valid = active
+            & !edge
+            & !(negative & positive)
+            & det.ne(zero)
+            & temp.lt(*t_max)
+            & temp.gt(Numx4::splat(t_min))
+            & temp.gt(t_error!(
+                (ax, ay, az),
+                (bx, by, bz),
+                (cx, cy, cz),
+                (u, v, w),
+                inv_det
+            ));
 
-                return true;
+        let beta = v * inv_det;
+        let gamma = w * inv_det;
+
+        for (lane, rec) in recs.iter_mut().enumerate() {
+            if valid.extract(lane) {
+                let (temp, u, v) = (temp.extract(lane), beta.extract(lane), gamma.extract(lane));
+                self.record(packet.ray(lane), temp, u, v, rec);
             }
         }
-        false
+        *t_max = valid.select(temp, *t_max);
+
+        let mut hit = valid;
+        for (lane, rec) in recs.iter_mut().enumerate() {
+            if edge.extract(lane) && self.hit(packet.ray(lane), t_min, t_max.extract(lane), rec) {
+                hit = hit.replace(lane, true);
+                *t_max = t_max.replace(lane, rec.t);
+            }
+        }
+
+        hit
     }
 
     fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
@@ -87,3 +423,16 @@ impl<T: Material> Hitable for Triangle<T> {
         Some(BoundingBox::new(a, b))
     }
 }
+
+impl<T: Material> Sampleable for Triangle<T> {
+    fn area(&self) -> Num {
+        0.5 * (self.p2 - self.p1).cross(self.p3 - self.p1).len()
+    }
+
+    fn sample(&self, u: Num, v: Num) -> (Vec3, Vec3) {
+        // Folding the square into the triangle with a square root keeps the points uniform.
+        let su = u.sqrt();
+        let (p, _) = barycentric_point([self.p1, self.p2, self.p3], su * (1.0 - v), su * v);
+        (p, geometric_normal(self.p1, self.p2, self.p3, self.normal))
+    }
+}
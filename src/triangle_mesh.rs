@@ -0,0 +1,387 @@
+use crate::bounding_box::BoundingBox;
+use crate::collections::bvh_tree::{self, BVHNode};
+use crate::hitable::{HitRecord, Hitable, TraversalStats};
+use crate::material::Material;
+use crate::num::*;
+use crate::ray::Ray;
+use crate::triangle::{self, ShearedRay};
+use crate::vec3::Vec3;
+
+use rand::prelude::*;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// A mesh of triangles sharing their vertices and material.
+///
+/// Each triangle is stored as three indices into the vertex buffer, and the mesh keeps its own
+/// BVH whose leaves reference the triangles by index.
+pub struct TriangleMesh<T: Material> {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<[Num; 2]>>,
+    nodes: Vec<BVHNode>,
+    material: T,
+}
+
+unsafe impl<T: Material> Sync for TriangleMesh<T> {}
+
+impl<T: Material> TriangleMesh<T> {
+    pub fn new(
+        vertices: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: T,
+        rng: &mut ThreadRng,
+    ) -> Self {
+        assert!(!triangles.is_empty());
+
+        let boxes = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let (p1, p2, p3) = (
+                    vertices[a as usize],
+                    vertices[b as usize],
+                    vertices[c as usize],
+                );
+                BoundingBox::new(p1.min(&p2.min(&p3)), p1.max(&p2.max(&p3)))
+            })
+            .collect::<Vec<_>>();
+
+        let (nodes, order) = bvh_tree::build(&boxes, rng);
+        let triangles = order.into_iter().map(|i| triangles[i]).collect();
+
+        TriangleMesh {
+            vertices,
+            triangles,
+            normals: None,
+            uvs: None,
+            nodes,
+            material,
+        }
+    }
+
+    /// Builds a mesh from a soup of triangles, merging the vertices with the same coordinates.
+    pub fn from_triangles(
+        triangles: impl IntoIterator<Item = [Vec3; 3]>,
+        material: T,
+        rng: &mut ThreadRng,
+    ) -> Self {
+        let (vertices, triangles) = merge_vertices(triangles);
+        Self::new(vertices, triangles, material, rng)
+    }
+
+    /// Sets a normal for every vertex, which are interpolated across the triangles to shade
+    /// them smoothly.
+    pub fn with_vertex_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.vertices.len());
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Sets the texture coordinates of every vertex. Without them, the texture coordinates of a
+    /// hit are its barycentric coordinates in the hit triangle.
+    pub fn with_uvs(mut self, uvs: Vec<[Num; 2]>) -> Self {
+        assert_eq!(uvs.len(), self.vertices.len());
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Computes smooth vertex normals, see `vertex_normals`. Vertices lying on a crease are
+    /// split so each side of the crease gets its own normal.
+    pub fn smooth(&mut self, crease_angle: Num) {
+        let corners = vertex_normals(&self.vertices, &self.triangles, crease_angle);
+        let positions = std::mem::take(&mut self.vertices);
+
+        let old_uvs = self.uvs.take();
+
+        let mut vertices = Vec::with_capacity(positions.len());
+        let mut normals = Vec::with_capacity(positions.len());
+        let mut uvs = Vec::with_capacity(positions.len());
+        let mut lookup = HashMap::new();
+
+        for (triangle, corner_normals) in self.triangles.iter_mut().zip(corners) {
+            for (index, normal) in triangle.iter_mut().zip(corner_normals.iter()) {
+                let key = (*index, key(*normal));
+                *index = *lookup.entry(key).or_insert_with(|| {
+                    vertices.push(positions[*index as usize]);
+                    normals.push(*normal);
+                    if let Some(old_uvs) = &old_uvs {
+                        uvs.push(old_uvs[*index as usize]);
+                    }
+                    u32::try_from(vertices.len() - 1).expect("too many vertices for u32 indices")
+                });
+            }
+        }
+
+        self.vertices = vertices;
+        self.normals = Some(normals);
+        self.uvs = old_uvs.map(|_| uvs);
+    }
+
+    /// The mesh as a solid, or `None` if it isn't watertight: every edge must be shared by
+    /// exactly two triangles going along it in opposite directions.
+    pub fn into_closed(self) -> Option<ClosedMesh<T>> {
+        // Edges are compared by position, as `smooth` splits the vertices on creases.
+        let mut edges = HashMap::new();
+        for triangle in &self.triangles {
+            let keys = [
+                key(self.vertices[triangle[0] as usize]),
+                key(self.vertices[triangle[1] as usize]),
+                key(self.vertices[triangle[2] as usize]),
+            ];
+            for i in 0..3 {
+                *edges.entry((keys[i], keys[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        let closed = edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1));
+        if closed {
+            Some(ClosedMesh(self))
+        } else {
+            None
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vec3] {
+        &self.vertices
+    }
+
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    #[inline(always)]
+    fn points(&self, i: usize) -> (Vec3, Vec3, Vec3) {
+        let [a, b, c] = self.triangles[i];
+        (
+            self.vertices[a as usize],
+            self.vertices[b as usize],
+            self.vertices[c as usize],
+        )
+    }
+
+    fn intersect(
+        &self,
+        i: usize,
+        r: &ShearedRay,
+        t_min: Num,
+        t_max: Num,
+    ) -> Option<(Num, Num, Num)> {
+        let (p1, p2, p3) = self.points(i);
+        r.intersect(p1, p2, p3, t_min, t_max)
+    }
+}
+
+impl<T: Material> Hitable for TriangleMesh<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.hit_counting(r, t_min, t_max, rec, &mut TraversalStats::default())
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        let sheared = ShearedRay::new(r);
+        let mut occluded = false;
+        let mut closest = t_max;
+        let mut stats = TraversalStats::default();
+        bvh_tree::traverse(
+            &self.nodes,
+            0,
+            r,
+            t_min,
+            &mut closest,
+            &mut stats,
+            |i, closest, _| {
+                occluded = self.intersect(i, &sheared, t_min, *closest).is_some();
+                occluded
+            },
+        );
+        occluded
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        let sheared = ShearedRay::new(r);
+        let mut hit = None;
+        let mut closest = t_max;
+        bvh_tree::traverse(
+            &self.nodes,
+            0,
+            r,
+            t_min,
+            &mut closest,
+            stats,
+            |i, closest, stats| {
+                stats.primitive_tests += 1;
+                if let Some((temp, u, v)) = self.intersect(i, &sheared, t_min, *closest) {
+                    hit = Some((i, u, v));
+                    *closest = temp;
+                }
+                false
+            },
+        );
+
+        match hit {
+            Some((i, u, v)) => {
+                let (p1, p2, p3) = self.points(i);
+                let geometric_normal = (p2 - p1).cross(p3 - p1).unit();
+                let normal = match &self.normals {
+                    Some(normals) => {
+                        let [a, b, c] = self.triangles[i];
+                        let normals = [
+                            normals[a as usize],
+                            normals[b as usize],
+                            normals[c as usize],
+                        ];
+                        triangle::interpolate(&normals, u, v)
+                    }
+                    None => geometric_normal,
+                };
+                let (p, p_error) = triangle::barycentric_point([p1, p2, p3], u, v);
+                rec.t = closest;
+                rec.p = p;
+                rec.p_error = p_error;
+                rec.set_normals(r, triangle::geometric_normal(p1, p2, p3, normal), normal);
+                let uvs = self.uvs.as_ref().map(|uvs| {
+                    let [a, b, c] = self.triangles[i];
+                    [uvs[a as usize], uvs[b as usize], uvs[c as usize]]
+                });
+                let (u, v, dpdu, dpdv) =
+                    triangle::texture_coordinates(uvs.as_ref(), [p1, p2, p3], u, v);
+                rec.u = u;
+                rec.v = v;
+                rec.dpdu = dpdu;
+                rec.dpdv = dpdv;
+                rec.primitive_id = i;
+                rec.instance_id = None;
+                rec.material = &self.material;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, _: Num, _: Num) -> Option<BoundingBox> {
+        Some(self.nodes[0].bounding_box.clone())
+    }
+}
+
+/// A watertight `TriangleMesh`, which makes it a solid.
+pub struct ClosedMesh<T: Material>(TriangleMesh<T>);
+
+impl<T: Material> Hitable for ClosedMesh<T> {
+    fn hit<'a>(&'a self, r: &Ray, t_min: Num, t_max: Num, rec: &mut HitRecord<'a>) -> bool {
+        self.0.hit(r, t_min, t_max, rec)
+    }
+
+    fn occluded(&self, r: &Ray, t_min: Num, t_max: Num) -> bool {
+        self.0.occluded(r, t_min, t_max)
+    }
+
+    fn hit_counting<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: Num,
+        t_max: Num,
+        rec: &mut HitRecord<'a>,
+        stats: &mut TraversalStats,
+    ) -> bool {
+        self.0.hit_counting(r, t_min, t_max, rec, stats)
+    }
+
+    fn bounding_box(&self, t0: Num, t1: Num) -> Option<BoundingBox> {
+        self.0.bounding_box(t0, t1)
+    }
+}
+
+/// A hashable key identifying a point by its coordinates.
+// The conversion is only needed when `Num` is `f32`.
+#[allow(clippy::useless_conversion)]
+fn key(p: Vec3) -> [u64; 3] {
+    // Adding zero turns negative zeros into positive ones so both compare equal.
+    [
+        u64::from((p.x() + 0.0).to_bits()),
+        u64::from((p.y() + 0.0).to_bits()),
+        u64::from((p.z() + 0.0).to_bits()),
+    ]
+}
+
+/// Turns a soup of triangles into a vertex buffer and an index buffer, merging the vertices with
+/// the same coordinates.
+pub(crate) fn merge_vertices(
+    triangles: impl IntoIterator<Item = [Vec3; 3]>,
+) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+    let mut vertices = Vec::new();
+    let mut lookup = HashMap::new();
+
+    let mut index = |p: Vec3| {
+        *lookup.entry(key(p)).or_insert_with(|| {
+            vertices.push(p);
+            u32::try_from(vertices.len() - 1).expect("too many vertices for u32 indices")
+        })
+    };
+
+    let triangles = triangles
+        .into_iter()
+        .map(|[p1, p2, p3]| [index(p1), index(p2), index(p3)])
+        .collect();
+
+    (vertices, triangles)
+}
+
+/// Computes a normal for each corner of each triangle by averaging the normals of the triangles
+/// sharing that vertex, weighted by the angle they span at it. Triangles whose normals differ
+/// by more than `crease_angle` radians do not contribute to each other's normals, which keeps
+/// sharp edges sharp.
+pub fn vertex_normals(
+    vertices: &[Vec3],
+    triangles: &[[u32; 3]],
+    crease_angle: Num,
+) -> Vec<[Vec3; 3]> {
+    let mut faces = vec![Vec::new(); vertices.len()];
+    let mut face_normals = Vec::with_capacity(triangles.len());
+
+    for (f, triangle) in triangles.iter().enumerate() {
+        let p = [
+            vertices[triangle[0] as usize],
+            vertices[triangle[1] as usize],
+            vertices[triangle[2] as usize],
+        ];
+        face_normals.push((p[1] - p[0]).cross(p[2] - p[0]).unit());
+        for k in 0..3 {
+            let e1 = (p[(k + 1) % 3] - p[k]).unit();
+            let e2 = (p[(k + 2) % 3] - p[k]).unit();
+            let angle = e1.dot(e2).clamp(-1.0, 1.0).acos();
+            faces[triangle[k] as usize].push((f, angle));
+        }
+    }
+
+    let cos_crease = crease_angle.cos();
+
+    triangles
+        .iter()
+        .enumerate()
+        .map(|(f, triangle)| {
+            let mut normals = [face_normals[f]; 3];
+            for (normal, &vertex) in normals.iter_mut().zip(triangle.iter()) {
+                let mut sum = Vec3::default();
+                for &(g, angle) in &faces[vertex as usize] {
+                    // Degenerate triangles have a NaN normal and are skipped.
+                    if face_normals[g].dot(face_normals[f]) >= cos_crease {
+                        sum += angle * face_normals[g];
+                    }
+                }
+                if sum.dot(sum) > 0.0 {
+                    *normal = sum.unit();
+                }
+            }
+            normals
+        })
+        .collect()
+}
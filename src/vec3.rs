@@ -1,5 +1,6 @@
 use crate::num::*;
-use packed_simd::*;
+
+use std::ops::{Index, IndexMut, Neg};
 
 macro_rules! impl_op {
     ($trait:tt, $method:tt) => {
@@ -86,6 +87,55 @@ impl Vec3 {
         self.dot(self).sqrt()
     }
 
+    pub fn len_squared(self) -> Num {
+        self.dot(self)
+    }
+
+    /// Linear interpolation, `self` when `t` is 0 and `other` when `t` is 1.
+    pub fn lerp(self, other: Self, t: Num) -> Self {
+        self + t * (other - self)
+    }
+
+    /// Reflects `self` on the surface with unit normal `n`.
+    pub fn reflect(self, n: Self) -> Self {
+        self - 2.0 * self.dot(n) * n
+    }
+
+    /// Refracts the unit vector `self` through the surface with unit normal `n`, facing against
+    /// `self`, where `ratio` is the ratio between the refractive indices of the media before and
+    /// after the surface. Returns `None` on total internal reflection.
+    pub fn refract(self, n: Self, ratio: Num) -> Option<Self> {
+        let cos = self.dot(n);
+        let discriminant = 1.0 - ratio * ratio * (1.0 - cos * cos);
+        if discriminant > 0.0 {
+            Some(ratio * (self - n * cos) - n * discriminant.sqrt())
+        } else {
+            None
+        }
+    }
+
+    pub fn abs(self) -> Self {
+        Vec3 {
+            inner: self.inner.abs(),
+        }
+    }
+
+    /// The index of the component with the largest absolute value.
+    pub fn max_axis(self) -> usize {
+        let (x, y, z) = (self.x().abs(), self.y().abs(), self.z().abs());
+        if x > y {
+            if x > z {
+                0
+            } else {
+                2
+            }
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+
     pub fn sqrt(self) -> Self {
         Vec3 {
             inner: self.inner.sqrt(),
@@ -121,6 +171,20 @@ impl Vec3 {
         self.inner.extract(2)
     }
 
+    pub(crate) fn get(&self, axis: usize) -> Num {
+        self.inner.extract(axis)
+    }
+
+    pub(crate) fn inner(self) -> Numx4 {
+        self.inner
+    }
+
+    pub(crate) fn from_inner(inner: Numx4) -> Self {
+        Vec3 {
+            inner: inner.replace(3, 0.0),
+        }
+    }
+
     pub fn r(&self) -> Num {
         self.inner.extract(0)
     }
@@ -157,3 +221,82 @@ impl Vec3 {
         )
     }
 }
+
+/// Compares the three components, ignoring the unused lane of the SIMD vector.
+impl PartialEq for Vec3 {
+    fn eq(&self, other: &Vec3) -> bool {
+        self.x() == other.x() && self.y() == other.y() && self.z() == other.z()
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Self::Output {
+        Vec3 { inner: -self.inner }
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = Num;
+
+    fn index(&self, axis: usize) -> &Num {
+        assert!(axis < 3, "index out of bounds: Vec3 has 3 components");
+        // The SIMD vector has the same layout as an array of its lanes.
+        let lanes = unsafe { &*(&self.inner as *const Numx4 as *const [Num; 4]) };
+        &lanes[axis]
+    }
+}
+
+impl IndexMut<usize> for Vec3 {
+    fn index_mut(&mut self, axis: usize) -> &mut Num {
+        assert!(axis < 3, "index out of bounds: Vec3 has 3 components");
+        let lanes = unsafe { &mut *(&mut self.inner as *mut Numx4 as *mut [Num; 4]) };
+        &mut lanes[axis]
+    }
+}
+
+/// An orthonormal basis, used to move vectors between world space and a frame aligned with a
+/// surface.
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// A basis whose `w` axis points along `n`, with the other two axes chosen arbitrarily.
+    pub fn from_w(n: Vec3) -> Self {
+        let w = n.unit();
+        // Duff et al., "Building an Orthonormal Basis, Revisited".
+        let sign = if w.z() >= 0.0 { 1.0 } else { -1.0 };
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        let u = Vec3::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vec3::new(b, sign + w.y() * w.y() * a, -w.y());
+        Onb { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Moves `a` from the basis to world space.
+    pub fn to_world(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Moves `a` from world space to the basis.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
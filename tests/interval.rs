@@ -0,0 +1,66 @@
+use ray_tracing::interval::Interval;
+
+#[test]
+fn basics() {
+    let i = Interval::new(1.0, 3.0);
+    assert_eq!(i.len(), 2.0);
+    assert_eq!(i.midpoint(), 2.0);
+    assert!(!i.is_empty());
+    assert!(i.contains(1.0) && i.contains(3.0) && i.contains(2.0));
+    assert!(!i.contains(0.5) && !i.contains(3.5));
+    assert!(i.surrounds(2.0) && !i.surrounds(1.0) && !i.surrounds(3.0));
+    assert_eq!(i.clamp(0.0), 1.0);
+    assert_eq!(i.clamp(5.0), 3.0);
+    assert_eq!(i.clamp(2.5), 2.5);
+    assert_eq!(i.expand(0.5), Interval::new(0.5, 3.5));
+    assert_eq!(Interval::point(2.0).len(), 0.0);
+}
+
+#[test]
+fn empty_and_universe() {
+    assert!(Interval::EMPTY.is_empty());
+    assert_eq!(Interval::EMPTY.len(), 0.0);
+    assert!(!Interval::EMPTY.contains(0.0));
+    assert_eq!(Interval::default(), Interval::EMPTY);
+    assert!(Interval::UNIVERSE.contains(1e30));
+    assert!(Interval::new(2.0, 1.0).is_empty());
+}
+
+#[test]
+fn set_operations() {
+    let a = Interval::new(0.0, 2.0);
+    let b = Interval::new(1.0, 3.0);
+    let c = Interval::new(5.0, 6.0);
+    assert_eq!(a.union(&b), Interval::new(0.0, 3.0));
+    assert_eq!(a.union(&c), Interval::new(0.0, 6.0));
+    assert_eq!(a.union(&Interval::EMPTY), a);
+    assert_eq!(Interval::EMPTY.union(&a), a);
+    assert_eq!(a.intersect(&b), Interval::new(1.0, 2.0));
+    assert!(a.intersect(&c).is_empty());
+    assert!(a.overlaps(&b) && !a.overlaps(&c));
+    assert!(a.intersect(&Interval::EMPTY).is_empty());
+    // Touching intervals overlap in one point.
+    assert!(a.overlaps(&Interval::new(2.0, 4.0)));
+}
+
+#[test]
+fn arithmetic() {
+    let a = Interval::new(1.0, 2.0);
+    let b = Interval::new(-3.0, 4.0);
+    assert_eq!(a + b, Interval::new(-2.0, 6.0));
+    assert_eq!(a - b, Interval::new(-3.0, 5.0));
+    assert_eq!(-b, Interval::new(-4.0, 3.0));
+    assert_eq!(a * b, Interval::new(-6.0, 8.0));
+    assert_eq!(b * b, Interval::new(-12.0, 16.0));
+    assert_eq!(a * 2.0, Interval::new(2.0, 4.0));
+    assert_eq!(a * -2.0, Interval::new(-4.0, -2.0));
+
+    // Every product of members is contained in the product of the intervals.
+    for &x in &[1.0, 1.5, 2.0] {
+        for &y in &[-3.0, 0.0, 2.5, 4.0] {
+            assert!((a * b).contains(x * y));
+            assert!((a + b).contains(x + y));
+            assert!((a - b).contains(x - y));
+        }
+    }
+}
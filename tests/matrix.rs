@@ -0,0 +1,142 @@
+use ray_tracing::matrix::{Mat3, Mat4};
+use ray_tracing::num::Num;
+use ray_tracing::vec3::Vec3;
+
+// Loose enough for both precisions.
+const EPSILON: Num = 1e4 * Num::EPSILON;
+
+fn assert_close3(a: &Mat3, b: &Mat3) {
+    assert!((*a - *b).max_norm() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+fn assert_close4(a: &Mat4, b: &Mat4) {
+    let (a, b) = (a.to_array(), b.to_array());
+    for i in 0..4 {
+        for j in 0..4 {
+            assert!((a[i][j] - b[i][j]).abs() < EPSILON, "{:?} != {:?}", a, b);
+        }
+    }
+}
+
+fn sample3() -> Mat3 {
+    Mat3::new([[2.0, -1.0, 0.5], [0.0, 3.0, 1.0], [4.0, 1.0, -2.0]])
+}
+
+fn sample4() -> Mat4 {
+    Mat4::new([
+        [2.0, -1.0, 0.5, 3.0],
+        [0.0, 3.0, 1.0, -1.0],
+        [4.0, 1.0, -2.0, 0.0],
+        [1.0, 0.0, 2.0, 1.0],
+    ])
+}
+
+#[test]
+fn mat3_rows_and_columns() {
+    let m = sample3();
+    assert_eq!(m.row(1), Vec3::new(0.0, 3.0, 1.0));
+    assert_eq!(m.col(1), Vec3::new(-1.0, 3.0, 1.0));
+    assert_eq!(m[2][0], 4.0);
+    assert_eq!(m.transpose().row(1), m.col(1));
+    assert_eq!(m.transpose().transpose(), m);
+    assert_eq!(
+        Mat3::from_cols(m.col(0), m.col(1), m.col(2)),
+        Mat3::from_rows(m.row(0), m.row(1), m.row(2))
+    );
+}
+
+#[test]
+fn mat3_products() {
+    let m = sample3();
+    let v = Vec3::new(1.0, 2.0, 3.0);
+    assert_eq!(m * v, Vec3::new(1.5, 9.0, 0.0));
+    assert_eq!(Mat3::identity() * m, m);
+    assert_eq!(m * Mat3::identity(), m);
+    assert_eq!(Mat3::diagonal(Vec3::new(1.0, 2.0, 3.0)) * v, v * v);
+
+    let n = Mat3::new([[1.0, 0.0, 2.0], [-1.0, 1.0, 0.0], [0.0, 2.0, 1.0]]);
+    // Associativity with vectors.
+    assert!(((m * n) * v - m * (n * v)).len() < EPSILON);
+    assert_eq!((m * n).transpose(), n.transpose() * m.transpose());
+    assert_eq!(m * 2.0, m + m);
+    assert_eq!(m - m, Mat3::identity() * 0.0);
+}
+
+#[test]
+fn mat3_determinant_and_inverse() {
+    let m = sample3();
+    assert!((m.determinant() - -24.0).abs() < EPSILON);
+    assert_eq!(Mat3::identity().determinant(), 1.0);
+    assert!((m.transpose().determinant() - m.determinant()).abs() < EPSILON);
+
+    let inv = m.inverse().unwrap();
+    assert_close3(&(m * inv), &Mat3::identity());
+    assert_close3(&(inv * m), &Mat3::identity());
+
+    let singular = Mat3::new([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]]);
+    assert_eq!(singular.determinant(), 0.0);
+    assert!(singular.inverse().is_none());
+}
+
+#[test]
+fn mat4_basics() {
+    let m = sample4();
+    assert_eq!(m.get(0, 3), 3.0);
+    assert_eq!(Mat4::new(m.to_array()), m);
+    assert_eq!(m.transpose().get(3, 0), 3.0);
+    assert_eq!(m.transpose().transpose(), m);
+    assert_eq!(Mat4::identity() * m, m);
+    assert_eq!(m * Mat4::identity(), m);
+    assert_eq!(Mat4::default(), Mat4::identity());
+}
+
+#[test]
+fn mat4_product() {
+    let a = sample4();
+    let b = sample4().transpose();
+    let (x, y) = (a.to_array(), b.to_array());
+    let product = (a * b).to_array();
+    for i in 0..4 {
+        for j in 0..4 {
+            let expected: Num = (0..4).map(|k| x[i][k] * y[k][j]).sum();
+            assert_eq!(product[i][j], expected);
+        }
+    }
+}
+
+#[test]
+fn mat4_inverse() {
+    let m = sample4();
+    let inv = m.inverse().unwrap();
+    assert_close4(&(m * inv), &Mat4::identity());
+    assert_close4(&(inv * m), &Mat4::identity());
+    assert_close4(&inv.inverse().unwrap(), &m);
+
+    // Needs pivoting, the first element is zero.
+    let p = Mat4::new([
+        [0.0, 1.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ]);
+    assert_close4(&(p * p.inverse().unwrap()), &Mat4::identity());
+
+    let mut singular = m.to_array();
+    singular[3] = singular[0];
+    assert!(Mat4::new(singular).inverse().is_none());
+}
+
+#[test]
+fn mat4_affine() {
+    let linear = sample3();
+    let translation = Vec3::new(1.0, -2.0, 3.0);
+    let m = Mat4::from_linear(&linear, translation);
+    assert_eq!(m.linear(), linear);
+    assert_eq!(m.translation(), translation);
+
+    let p = Vec3::new(0.5, 1.5, -1.0);
+    assert_eq!(m.point(p), linear * p + translation);
+    assert_eq!(m.vector(p), linear * p);
+    assert_eq!(m.transpose_vector(p), linear.transpose() * p);
+    assert_eq!(m.point(Vec3::default()), translation);
+}
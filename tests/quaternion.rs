@@ -0,0 +1,94 @@
+use ray_tracing::matrix::Mat3;
+use ray_tracing::num::Num;
+use ray_tracing::quaternion::Quaternion;
+use ray_tracing::vec3::Vec3;
+
+// Loose enough for both precisions.
+const EPSILON: Num = 1e4 * Num::EPSILON;
+
+fn assert_close(a: Vec3, b: Vec3) {
+    assert!((a - b).len() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+    // `q` and `-q` are the same rotation.
+    assert!((a.dot(b).abs() - 1.0).abs() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn identity() {
+    let q = Quaternion::identity();
+    let v = Vec3::new(1.0, 2.0, 3.0);
+    assert_eq!(q.rotate(v), v);
+    assert_eq!(q.to_mat3(), Mat3::identity());
+    assert_eq!(q * q, q);
+    assert_eq!(Quaternion::default(), q);
+}
+
+#[test]
+fn axis_angle() {
+    let q = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 2.0), 90.0);
+    assert!((q.len() - 1.0).abs() < EPSILON);
+    assert_close(q.rotate(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+    assert_close(q.rotate(Vec3::new(0.0, 0.0, 1.0)), Vec3::new(0.0, 0.0, 1.0));
+
+    let q = Quaternion::from_axis_angle(Vec3::new(1.0, 1.0, 1.0), 120.0);
+    assert_close(q.rotate(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+    assert_close(q.rotate(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn composition_and_conjugate() {
+    let a = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 30.0);
+    let b = Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 70.0);
+    let v = Vec3::new(0.3, -1.0, 2.0);
+    assert_close((a * b).rotate(v), a.rotate(b.rotate(v)));
+    assert_close(a.conjugate().rotate(a.rotate(v)), v);
+    assert_same_rotation(a * a.conjugate(), Quaternion::identity());
+
+    let twice = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 60.0);
+    assert_same_rotation(a * a, twice);
+}
+
+#[test]
+fn matrices() {
+    let q = Quaternion::from_axis_angle(Vec3::new(0.2, -0.5, 1.0), 145.0);
+    let m = q.to_mat3();
+    let v = Vec3::new(1.0, -2.0, 0.5);
+    assert_close(m * v, q.rotate(v));
+    assert!((m.determinant() - 1.0).abs() < EPSILON);
+    assert!((m * m.transpose() - Mat3::identity()).max_norm() < EPSILON);
+
+    // Every branch of the conversion back.
+    let axes = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(1.0, 2.0, 3.0),
+    ];
+    for &axis in &axes {
+        for &angle in &[0.0, 45.0, 179.0, 180.0, 270.0] {
+            let q = Quaternion::from_axis_angle(axis, angle);
+            assert_same_rotation(Quaternion::from_mat3(&q.to_mat3()), q);
+        }
+    }
+}
+
+#[test]
+fn slerp() {
+    let axis = Vec3::new(0.0, 0.0, 1.0);
+    let a = Quaternion::from_axis_angle(axis, 10.0);
+    let b = Quaternion::from_axis_angle(axis, 110.0);
+    assert_same_rotation(a.slerp(b, 0.0), a);
+    assert_same_rotation(a.slerp(b, 1.0), b);
+    assert_same_rotation(a.slerp(b, 0.5), Quaternion::from_axis_angle(axis, 60.0));
+    assert_same_rotation(a.slerp(b, 0.25), Quaternion::from_axis_angle(axis, 35.0));
+
+    // Takes the shortest arc even when the quaternions are in opposite hemispheres.
+    let c = -Quaternion::from_axis_angle(axis, 30.0);
+    assert_same_rotation(a.slerp(c, 0.5), Quaternion::from_axis_angle(axis, 20.0));
+
+    // Nearly equal rotations.
+    let d = Quaternion::from_axis_angle(axis, 10.0001);
+    assert!((a.slerp(d, 0.5).len() - 1.0).abs() < EPSILON);
+}
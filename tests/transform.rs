@@ -0,0 +1,107 @@
+use ray_tracing::matrix::Mat4;
+use ray_tracing::num::Num;
+use ray_tracing::transform::{AnimatedTransform, Transform};
+use ray_tracing::vec3::Vec3;
+
+// Loose enough for both precisions.
+const EPSILON: Num = 1e4 * Num::EPSILON;
+
+fn assert_close(a: Vec3, b: Vec3) {
+    assert!((a - b).len() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+fn assert_same(a: &Transform, b: &Transform) {
+    let (a, b) = (a.matrix().to_array(), b.matrix().to_array());
+    for i in 0..4 {
+        for j in 0..4 {
+            assert!((a[i][j] - b[i][j]).abs() < EPSILON, "{:?} != {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn inverse() {
+    let transforms = [
+        Transform::translate(Vec3::new(1.0, -2.0, 3.0)),
+        Transform::scale(Vec3::new(2.0, 0.5, -1.0)),
+        Transform::rotate(Vec3::new(1.0, 2.0, -0.5), 37.0),
+        Transform::look_at(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-1.0, 0.0, 4.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+    ];
+    let p = Vec3::new(0.3, -0.7, 1.1);
+    for t in &transforms {
+        assert_close(t.inverse().point(t.point(p)), p);
+        assert_close(t.inverse().vector(t.vector(p)), p);
+        let from_matrix = Transform::from_matrix(*t.matrix()).unwrap();
+        assert_close(from_matrix.inverse().point(t.point(p)), p);
+    }
+    assert!(Transform::from_matrix(Mat4::new([[0.0; 4]; 4])).is_none());
+}
+
+#[test]
+fn composition() {
+    let t = Transform::translate(Vec3::new(1.0, 0.0, 0.0));
+    let r = Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 90.0);
+    let p = Vec3::new(1.0, 0.0, 0.0);
+    assert_close((&t * &r).point(p), Vec3::new(1.0, 1.0, 0.0));
+    assert_close((&r * &t).point(p), Vec3::new(0.0, 2.0, 0.0));
+    let c = &t * &r;
+    assert_close(c.inverse().point(c.point(p)), p);
+}
+
+#[test]
+fn normals() {
+    let s = Transform::scale(Vec3::new(2.0, 1.0, 1.0));
+    // The plane x = y, with normal (1, -1, 0), becomes x = 2y.
+    let n = s.normal(Vec3::new(1.0, -1.0, 0.0));
+    let tangent = s.vector(Vec3::new(1.0, 1.0, 0.0));
+    assert!(n.dot(tangent).abs() < EPSILON);
+}
+
+#[test]
+fn look_at() {
+    let eye = Vec3::new(1.0, 2.0, 3.0);
+    let l = Transform::look_at(eye, Vec3::new(4.0, 2.0, 3.0), Vec3::new(0.0, 1.0, 0.0));
+    assert_close(l.point(Vec3::default()), eye);
+    assert_close(
+        l.vector(Vec3::new(0.0, 0.0, -1.0)),
+        Vec3::new(1.0, 0.0, 0.0),
+    );
+    assert_close(l.vector(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+}
+
+#[test]
+fn animation() {
+    let axis = Vec3::new(0.0, 1.0, 0.0);
+    let start = &Transform::translate(Vec3::new(1.0, 2.0, 3.0))
+        * &(&Transform::rotate(axis, 20.0) * &Transform::scale(Vec3::new(2.0, 1.0, 0.5)));
+    let end = &Transform::translate(Vec3::new(-1.0, 0.0, 3.0))
+        * &(&Transform::rotate(axis, 80.0) * &Transform::scale(Vec3::new(1.0, 1.0, 1.0)));
+    let middle = &Transform::translate(Vec3::new(0.0, 1.0, 3.0))
+        * &(&Transform::rotate(axis, 50.0) * &Transform::scale(Vec3::new(1.5, 1.0, 0.75)));
+
+    let animated = AnimatedTransform::new(start.clone(), 0.0, end.clone(), 2.0);
+    assert!(animated.is_animated());
+    assert_same(&animated.at(-1.0), &start);
+    assert_same(&animated.at(0.0), &start);
+    assert_same(&animated.at(1.0), &middle);
+    assert_same(&animated.at(2.0), &end);
+
+    // Reflections survive the decomposition.
+    let mirror = Transform::scale(Vec3::new(-1.0, 1.0, 1.0));
+    let still = AnimatedTransform::new(mirror.clone(), 0.0, mirror.clone(), 1.0);
+    assert!(!still.is_animated());
+    let spinning = AnimatedTransform::new(
+        mirror.clone(),
+        0.0,
+        &mirror * &Transform::rotate(axis, 90.0),
+        1.0,
+    );
+    assert_same(
+        &spinning.at(0.5),
+        &(&mirror * &Transform::rotate(axis, 45.0)),
+    );
+}
@@ -0,0 +1,171 @@
+use ray_tracing::num::Num;
+use ray_tracing::vec3::{Onb, Vec3};
+
+// Loose enough for both precisions.
+const EPSILON: Num = 1e4 * Num::EPSILON;
+
+fn assert_close(a: Vec3, b: Vec3) {
+    assert!((a - b).len() < EPSILON, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn arithmetic() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let b = Vec3::new(4.0, -5.0, 6.0);
+    assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+    assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+    assert_eq!(a * b, Vec3::new(4.0, -10.0, 18.0));
+    assert_eq!(b / a, Vec3::new(4.0, -2.5, 2.0));
+    assert_eq!(2.0 * a, Vec3::new(2.0, 4.0, 6.0));
+    assert_eq!(a / 2.0, Vec3::new(0.5, 1.0, 1.5));
+
+    let mut c = a;
+    c += b;
+    c -= a;
+    assert_eq!(c, b);
+    c *= 2.0;
+    c /= 2.0;
+    assert_eq!(c, b);
+}
+
+#[test]
+fn neg() {
+    let a = Vec3::new(1.0, -2.0, 3.0);
+    assert_eq!(-a, Vec3::new(-1.0, 2.0, -3.0));
+    assert_eq!(-(-a), a);
+    assert_eq!(a + -a, Vec3::default());
+}
+
+#[test]
+fn index() {
+    let mut a = Vec3::new(1.0, 2.0, 3.0);
+    assert_eq!((a[0], a[1], a[2]), (1.0, 2.0, 3.0));
+    a[1] = 5.0;
+    assert_eq!(a, Vec3::new(1.0, 5.0, 3.0));
+    assert_eq!(a.y(), 5.0);
+}
+
+#[test]
+#[should_panic]
+fn index_out_of_bounds() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let _ = a[3];
+}
+
+#[test]
+fn equality_ignores_the_unused_lane() {
+    // Adding a scalar also writes the fourth SIMD lane.
+    let a = Vec3::new(1.0, 1.0, 1.0);
+    let b = Vec3::default() + 1.0;
+    assert_eq!(a, b);
+}
+
+#[test]
+fn products() {
+    let a = Vec3::new(1.0, 2.0, 3.0);
+    let b = Vec3::new(4.0, 5.0, 6.0);
+    assert_eq!(a.dot(b), 32.0);
+    assert_eq!(a.cross(b), Vec3::new(-3.0, 6.0, -3.0));
+    assert_eq!(a.cross(b).dot(a), 0.0);
+    assert_eq!(a.cross(b), -b.cross(a));
+
+    let x = Vec3::new(1.0, 0.0, 0.0);
+    let y = Vec3::new(0.0, 1.0, 0.0);
+    assert_eq!(x.cross(y), Vec3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn lengths() {
+    let a = Vec3::new(2.0, 3.0, 6.0);
+    assert_eq!(a.len_squared(), 49.0);
+    assert_eq!(a.len(), 7.0);
+    assert!((a.unit().len() - 1.0).abs() < EPSILON);
+    assert_close(a.unit() * 7.0, a);
+    // The unused lane doesn't leak into the length.
+    assert_eq!((a + 1.0 - 1.0).len_squared(), 49.0);
+}
+
+#[test]
+fn lerp() {
+    let a = Vec3::new(0.0, 10.0, -4.0);
+    let b = Vec3::new(2.0, 20.0, 4.0);
+    assert_eq!(a.lerp(b, 0.0), a);
+    assert_eq!(a.lerp(b, 1.0), b);
+    assert_eq!(a.lerp(b, 0.5), Vec3::new(1.0, 15.0, 0.0));
+}
+
+#[test]
+fn min_max_abs() {
+    let a = Vec3::new(1.0, -5.0, 3.0);
+    let b = Vec3::new(2.0, -6.0, 0.0);
+    assert_eq!(a.min(&b), Vec3::new(1.0, -6.0, 0.0));
+    assert_eq!(a.max(&b), Vec3::new(2.0, -5.0, 3.0));
+    assert_eq!(a.min_max(&b), (a.min(&b), a.max(&b)));
+    assert_eq!(a.abs(), Vec3::new(1.0, 5.0, 3.0));
+    assert_eq!(a.max_axis(), 1);
+    assert_eq!(Vec3::new(0.0, 0.0, -1.0).max_axis(), 2);
+    assert_eq!(Vec3::new(-2.0, 1.0, 1.0).max_axis(), 0);
+}
+
+#[test]
+fn reflect() {
+    let n = Vec3::new(0.0, 1.0, 0.0);
+    let v = Vec3::new(1.0, -1.0, 0.0);
+    assert_eq!(v.reflect(n), Vec3::new(1.0, 1.0, 0.0));
+    // Reflecting twice gives the original vector back.
+    let v = Vec3::new(0.3, -0.4, 0.5);
+    let n = Vec3::new(1.0, 2.0, -1.0).unit();
+    assert_close(v.reflect(n).reflect(n), v);
+    assert!((v.reflect(n).len() - v.len()).abs() < EPSILON);
+}
+
+#[test]
+fn refract() {
+    let n = Vec3::new(0.0, 1.0, 0.0);
+
+    // Straight through at normal incidence.
+    let down = Vec3::new(0.0, -1.0, 0.0);
+    assert_close(down.refract(n, 1.0 / 1.5).unwrap(), down);
+
+    // Snell's law.
+    let v = Vec3::new(1.0, -1.0, 0.0).unit();
+    let ratio = 1.0 / 1.5;
+    let t = v.refract(n, ratio).unwrap();
+    assert!((t.len() - 1.0).abs() < EPSILON);
+    let sin_in = v.cross(n).len();
+    let sin_out = t.cross(n).len();
+    assert!((sin_in * ratio - sin_out).abs() < EPSILON);
+    assert!(t.y() < 0.0);
+
+    // Total internal reflection.
+    let grazing = Vec3::new(1.0, -0.1, 0.0).unit();
+    assert!(grazing.refract(n, 1.5).is_none());
+}
+
+#[test]
+fn orthonormal_basis() {
+    let normals = [
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.3, -0.8, 0.2),
+        Vec3::new(-1e-9, 1e-9, -1.0),
+    ];
+    for &n in &normals {
+        let onb = Onb::from_w(n);
+        let (u, v, w) = (onb.u(), onb.v(), onb.w());
+        assert_close(w, n.unit());
+        for &(a, b) in &[(u, v), (v, w), (w, u)] {
+            assert!(a.dot(b).abs() < EPSILON);
+        }
+        for &a in &[u, v, w] {
+            assert!((a.len() - 1.0).abs() < EPSILON);
+        }
+        // Right handed.
+        assert_close(u.cross(v), w);
+
+        let a = Vec3::new(0.5, -2.0, 3.0);
+        assert_close(onb.to_local(onb.to_world(a)), a);
+        assert_close(onb.to_world(Vec3::new(0.0, 0.0, 1.0)), w);
+    }
+}